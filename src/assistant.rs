@@ -0,0 +1,862 @@
+use crate::{
+    clock::{SharedClock, SystemClock, Ticker},
+    cloudevents::EventType,
+    config::{AssistantConfig, NotificationMode, PayloadEncoding, Topic},
+    consistency,
+    delivery::Delivery,
+    dry_run::{DryRun, DryRunOutput, Payload},
+    endpoint::ApiServerEndpoint,
+    error::AssistantError,
+    gaianet::NodeConfig,
+    gguf::{load_metadata, GgufMetadata},
+    health::{update_health, HealthChecker, HttpProber, Issue, Prober},
+    http,
+    hub::HubUrls,
+    identity::NodeIdentity,
+    info::{push_server_info, retrieve_server_info},
+    integrity::ModelsLock,
+    models::{node_models, Fingerprint, HashCache, ModelFile, ModelKind},
+    notification::HealthNotifier,
+    schedule::{Schedules, Task},
+    shutdown::{Shutdown, ShutdownReason, SHUTDOWN_TIMEOUT},
+    ApiClient, ApiKey, HealthStats, Issues, ProbeSettings, ServerHealth, ServerInfo, ServerLogFile,
+    SharedSchedules, Subscribers,
+};
+use log::{error, info, warn};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{
+    sync::{broadcast, Notify, RwLock},
+    task::JoinError,
+};
+
+// capacity of the event channel. Slow receivers miss the oldest events
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+pub(crate) type EventSender = broadcast::Sender<Event>;
+
+/// Loads the configuration again when the assistant is reloaded.
+pub type ConfigLoader = Arc<dyn Fn() -> Result<AssistantConfig, AssistantError> + Send + Sync>;
+
+/// Change observed by the assistant.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// The health of the API server changed, or was determined for the first time
+    HealthChanged { healthy: bool },
+    /// Server information was retrieved from the API server
+    InfoUpdated(Value),
+    /// The configuration and the node settings were reloaded
+    Reloaded,
+    /// A model file changed. The hashes are `None` if the file is missing
+    ModelChanged {
+        kind: ModelKind,
+        path: PathBuf,
+        old_sha256: Option<String>,
+        new_sha256: Option<String>,
+    },
+    /// A model file does not match its expected sha256. The server is reported unhealthy
+    /// until it matches again
+    ModelHashMismatch {
+        kind: ModelKind,
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    /// The assistant is shutting down, the final notification follows
+    ShuttingDown(ShutdownReason),
+}
+
+// Inputs of the server information besides `/v1/info`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct InfoExtras {
+    pub(crate) system_prompt: String,
+    pub(crate) rag_prompt: String,
+    pub(crate) sha256_chat_model: String,
+    pub(crate) sha256_embedding_model: String,
+    pub(crate) gguf_chat_model: Option<GgufMetadata>,
+    pub(crate) gguf_embedding_model: Option<GgufMetadata>,
+}
+impl InfoExtras {
+    pub(crate) fn sha256(&self, kind: ModelKind) -> &str {
+        match kind {
+            ModelKind::Chat => &self.sha256_chat_model,
+            ModelKind::Embedding => &self.sha256_embedding_model,
+        }
+    }
+
+    pub(crate) fn set_sha256(&mut self, kind: ModelKind, sha256: String) {
+        match kind {
+            ModelKind::Chat => self.sha256_chat_model = sha256,
+            ModelKind::Embedding => self.sha256_embedding_model = sha256,
+        }
+    }
+
+    pub(crate) fn set_gguf(&mut self, kind: ModelKind, gguf: Option<GgufMetadata>) {
+        match kind {
+            ModelKind::Chat => self.gguf_chat_model = gguf,
+            ModelKind::Embedding => self.gguf_embedding_model = gguf,
+        }
+    }
+}
+
+/// Monitors a LlamaEdge API server and reports its information and health to subscribers.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), server_assistant::error::AssistantError> {
+/// use server_assistant::{Assistant, ShutdownReason};
+///
+/// let assistant = Assistant::builder()
+///     .gaianet_dir("/home/user/gaianet")
+///     .build()
+///     .await?;
+///
+/// let mut events = assistant.subscribe();
+/// tokio::spawn(async move {
+///     while let Ok(event) = events.recv().await {
+///         println!("{:?}", event);
+///     }
+/// });
+///
+/// // stop on Ctrl-C, telling the subscribers that the server is going down
+/// let handle = assistant.clone();
+/// tokio::spawn(async move {
+///     let _ = tokio::signal::ctrl_c().await;
+///     handle.shutdown(ShutdownReason::AssistantShutdown);
+/// });
+///
+/// assistant.run().await
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Assistant {
+    pub(crate) server_addr: ApiServerEndpoint,
+    // connects to the Unix domain socket of the API server, if any
+    pub(crate) api_client: ApiClient,
+    // bearer token of the requests to the API server
+    pub(crate) api_key: ApiKey,
+    pub(crate) server_log_file: ServerLogFile,
+    pub(crate) gaianet_dir: Option<PathBuf>,
+    pub(crate) config: Arc<RwLock<AssistantConfig>>,
+    pub(crate) config_loader: Option<ConfigLoader>,
+    pub(crate) hot_reload: bool,
+    pub(crate) watched_files: Vec<PathBuf>,
+    pub(crate) schedules: SharedSchedules,
+    pub(crate) probe: ProbeSettings,
+    // re-arms the tickers after the schedules changed
+    pub(crate) rearm: Arc<Notify>,
+    // prompts set explicitly, taking precedence over config.json
+    pub(crate) system_prompt: Option<String>,
+    pub(crate) rag_prompt: Option<String>,
+    pub(crate) extras: Arc<RwLock<InfoExtras>>,
+    // model files, hashed in the background unless cached
+    pub(crate) models: Arc<RwLock<Vec<ModelFile>>>,
+    pub(crate) hash_cache: Option<PathBuf>,
+    pub(crate) hub_urls: Arc<RwLock<Option<HubUrls>>>,
+    // settings of config.json the server information is checked against
+    pub(crate) node: Arc<RwLock<Option<NodeConfig>>>,
+    pub(crate) info_subscribers: Subscribers,
+    pub(crate) health_subscribers: Subscribers,
+    pub(crate) server_info: ServerInfo,
+    pub(crate) server_health: ServerHealth,
+    pub(crate) issues: Issues,
+    // what the health checker observed of the API server
+    pub(crate) stats: HealthStats,
+    pub(crate) clock: SharedClock,
+    pub(crate) prober: Arc<dyn Prober>,
+    pub(crate) events: EventSender,
+    pub(crate) shutdown: Shutdown,
+    // payloads are written out instead of being sent, if set
+    pub(crate) dry_run: Option<Arc<DryRun>>,
+    // signs the payloads with the node key and the webhook secrets, and encodes them
+    pub(crate) delivery: Arc<Delivery>,
+}
+impl Assistant {
+    pub fn builder() -> AssistantBuilder {
+        AssistantBuilder::default()
+    }
+
+    /// Address of the monitored API server
+    pub fn server_addr(&self) -> &ApiServerEndpoint {
+        &self.server_addr
+    }
+
+    /// Configuration in effect
+    pub async fn config(&self) -> AssistantConfig {
+        self.config.read().await.clone()
+    }
+
+    /// Latest server information, if retrieved
+    pub async fn server_info(&self) -> Option<Value> {
+        self.server_info.read().await.clone()
+    }
+
+    /// Latest server health, if determined
+    pub async fn server_health(&self) -> Option<bool> {
+        *self.server_health.read().await
+    }
+
+    /// Subscribe to the changes observed from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Stop [`run`](Self::run): probing stops, pushes in flight complete, then the health
+    /// subscribers are told that the server is going down with `reason`.
+    pub fn shutdown(&self, reason: ShutdownReason) {
+        info!("Shutdown requested: {}", reason);
+        self.shutdown.request(reason);
+    }
+
+    /// Retrieve and push the server information, then check and push the server health
+    /// periodically. Returns after [`shutdown`](Self::shutdown), or with the error of the
+    /// first task that fails, e.g. when the log file of the API server cannot be read; the
+    /// other tasks are then stopped.
+    pub async fn run(&self) -> Result<(), AssistantError> {
+        // push the server information, then push it again once the models are hashed
+        let assistant = self.clone();
+        let push_info_handle = tokio::spawn(async move {
+            let (pushed, hashed) = tokio::join!(assistant.refresh_info(), assistant.hash_models());
+            match hashed {
+                true => assistant.refresh_info().await,
+                false => pushed,
+            }
+        });
+
+        // check server health periodically
+        let health_checker = HealthChecker::new(
+            Arc::clone(&self.server_log_file),
+            self.ticker(Task::LogScan),
+            Arc::clone(&self.server_health),
+            self.events.clone(),
+            Arc::clone(&self.clock),
+            Arc::clone(&self.prober),
+            self.ticker(Task::Probe),
+            Arc::clone(&self.issues),
+            Arc::clone(&self.stats),
+        );
+        let server_health = Arc::clone(&self.server_health);
+        let events = self.events.clone();
+        let shutdown = self.shutdown.clone();
+        let health_check_handle = tokio::spawn(async move {
+            // a probe in flight is abandoned on shutdown
+            let result = tokio::select! {
+                result = health_checker.run() => result,
+                _ = shutdown.requested() => Ok(()),
+            };
+            if let Err(e) = result {
+                update_health(&server_health, &events, false).await;
+
+                let err_msg = format!("Failed to check server health: {}", e);
+
+                error!("{}", &err_msg);
+
+                return Err(AssistantError::Operation(err_msg));
+            }
+
+            Ok(())
+        });
+
+        // push server health periodically, or on change
+        let notifications = self.config.read().await.notifications.clone();
+        let notifier = HealthNotifier::new(
+            Arc::clone(&self.health_subscribers),
+            Arc::clone(&self.server_health),
+            Arc::clone(&self.issues),
+            Arc::clone(&self.stats),
+            Arc::clone(&self.node),
+            notifications.mode,
+            notifications.schema,
+            self.clock.now(),
+            Arc::clone(&self.clock),
+            self.shutdown.clone(),
+            self.dry_run.clone(),
+            Arc::clone(&self.delivery),
+        );
+        let assistant = self.clone();
+        let health_notifier = notifier.clone();
+        let health_notify_handle = tokio::spawn(async move {
+            match health_notifier.mode {
+                NotificationMode::Periodic => {
+                    health_notifier
+                        .periodic(assistant.ticker(Task::HealthPush))
+                        .await
+                }
+                NotificationMode::OnChange => {
+                    health_notifier
+                        .on_change(
+                            assistant.ticker(Task::LogScan),
+                            assistant.ticker(Task::Heartbeat),
+                            &assistant.events,
+                        )
+                        .await
+                }
+            }
+        });
+
+        // reload on SIGHUP or file changes
+        let assistant = self.clone();
+        let reload_handle = tokio::spawn(async move {
+            match assistant.hot_reload {
+                true => {
+                    tokio::select! {
+                        result = assistant.clone().watch_for_reload() => result,
+                        _ = assistant.shutdown.requested() => Ok(()),
+                    }
+                }
+                false => Ok(()),
+            }
+        });
+
+        // hash the models again when they change, and verify them periodically
+        let assistant = self.clone();
+        let models_handle = tokio::spawn(async move {
+            tokio::select! {
+                _ = assistant.watch_models() => {}
+                _ = assistant.verify_models() => {}
+                _ = assistant.shutdown.requested() => {}
+            }
+        });
+
+        // retrieve and push the server information again on the info refresh schedule
+        let assistant = self.clone();
+        let refresh_info_handle = tokio::spawn(async move {
+            let ticker = assistant.ticker(Task::InfoRefresh);
+            let refresh = async {
+                loop {
+                    ticker.wait().await;
+                    // failures are logged, and retried on the next run
+                    let _ = assistant.refresh_info().await;
+                }
+            };
+            tokio::select! {
+                _ = refresh => {}
+                _ = assistant.shutdown.requested() => {}
+            }
+        });
+
+        // the tasks besides the info push only complete on shutdown, or with their own error
+        let health_notify_handle = async move { health_notify_handle.await.map(Ok) };
+        let models_handle = async move { models_handle.await.map(Ok) };
+        let refresh_info_handle = async move { refresh_info_handle.await.map(Ok) };
+        if let Err(e) = tokio::try_join!(
+            joined(health_check_handle),
+            joined(health_notify_handle),
+            joined(reload_handle),
+            joined(models_handle),
+            joined(refresh_info_handle)
+        ) {
+            // stop the tasks still running
+            self.shutdown.request(ShutdownReason::AssistantShutdown);
+
+            return Err(e);
+        }
+
+        let reason = self.shutdown.requested().await;
+        let _ = self.events.send(Event::ShuttingDown(reason));
+
+        // flush the server information still being pushed
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, push_info_handle)
+            .await
+            .is_err()
+        {
+            error!("Gave up pushing the server information on shutdown.");
+        }
+
+        notifier.notify_shutdown(reason).await;
+        info!("Server assistant stopped: {}", reason);
+
+        Ok(())
+    }
+
+    // Retrieve the server information, then push it to all subscribers
+    pub(crate) async fn refresh_info(&self) -> Result<(), AssistantError> {
+        self.refresh_info_as(EventType::InfoUpdated).await
+    }
+
+    // Same as `refresh_info`, the payloads being sent as events of `event_type`
+    pub(crate) async fn refresh_info_as(
+        &self,
+        event_type: EventType,
+    ) -> Result<(), AssistantError> {
+        // retrieve server information
+        let extras = self.extras.read().await.clone();
+        let api_client = self.api_client.read().await.clone();
+        let api_key = self.api_key.read().await.clone();
+        let result =
+            retrieve_server_info(&self.server_addr, &extras, &api_client, api_key.as_deref()).await;
+        match &result {
+            Err(AssistantError::Unauthorized(_)) => {
+                self.issues.write().await.insert(Issue::AuthMisconfigured);
+            }
+            Ok(_) => {
+                self.issues.write().await.remove(&Issue::AuthMisconfigured);
+            }
+            Err(_) => {}
+        }
+        let mut server_info = result?;
+        self.check_consistency(&mut server_info).await;
+
+        // store the server information
+        *self.server_info.write().await = Some(server_info.clone());
+        let _ = self.events.send(Event::InfoUpdated(server_info.clone()));
+
+        if let Some(dry_run) = &self.dry_run {
+            let subscribers = self.info_subscribers.read().await;
+            dry_run.write(Payload::DeviceInfo, subscribers.iter(), &server_info);
+            return Ok(());
+        }
+
+        // push server information to all subscribers
+        let retries = self.config.read().await.hub.retries;
+        let device_id = self
+            .node
+            .read()
+            .await
+            .as_ref()
+            .map(|node| node.device_id().to_string());
+        match push_server_info(
+            Arc::clone(&self.info_subscribers),
+            &server_info,
+            retries,
+            &self.delivery,
+            event_type,
+            device_id.as_deref(),
+        )
+        .await
+        {
+            Ok(_) => {
+                info!("Server information sent to subscribers successfully!");
+                Ok(())
+            }
+            Err(e) => {
+                let err_msg = format!("Failed to push server info to subscribers. {}", e);
+
+                error!("{}", &err_msg);
+
+                Err(AssistantError::Operation(err_msg))
+            }
+        }
+    }
+
+    // Report the inconsistencies between the server information and config.json as
+    // warnings, and degrade the health until they are fixed
+    async fn check_consistency(&self, server_info: &mut Value) {
+        let warnings = match &*self.node.read().await {
+            Some(node) => consistency::check(server_info, &node.config),
+            None => return,
+        };
+
+        let mut issues = self.issues.write().await;
+        issues.retain(|issue| !matches!(issue, Issue::ConfigMismatch(_)));
+        for warning in warnings.iter() {
+            warn!("{}: {}", warning.reason, warning.message);
+            issues.insert(Issue::ConfigMismatch(warning.mismatch));
+        }
+        if !warnings.is_empty() {
+            server_info["warnings"] = serde_json::json!(warnings);
+        }
+    }
+
+    pub(crate) fn ticker(&self, task: Task) -> Ticker {
+        Ticker::new(
+            Arc::clone(&self.schedules),
+            task,
+            Arc::clone(&self.rearm),
+            Arc::clone(&self.clock),
+        )
+    }
+}
+
+/// Builder for [`Assistant`].
+///
+/// With [`gaianet_dir`](Self::gaianet_dir), the log file, prompts and model hashes are read
+/// from the gaianet directory, and the hub is subscribed to the server information and
+/// health. Values set explicitly take precedence.
+#[derive(Default)]
+pub struct AssistantBuilder {
+    config: AssistantConfig,
+    config_loader: Option<ConfigLoader>,
+    hot_reload: bool,
+    watched_files: Vec<PathBuf>,
+    gaianet_dir: Option<PathBuf>,
+    server_log_file: Option<PathBuf>,
+    system_prompt: Option<String>,
+    rag_prompt: Option<String>,
+    info_subscribers: Vec<String>,
+    health_subscribers: Vec<String>,
+    clock: Option<SharedClock>,
+    prober: Option<Arc<dyn Prober>>,
+    dry_run: Option<DryRunOutput>,
+    identity: Option<NodeIdentity>,
+    secrets: HashMap<String, String>,
+    encodings: HashMap<String, PayloadEncoding>,
+}
+impl AssistantBuilder {
+    /// Settings not set explicitly with the other methods. Replaces the settings set so far
+    pub fn config(mut self, config: AssistantConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Load the configuration again on [`Assistant::reload`]. Without a loader, reloading
+    /// only reads the node settings again
+    pub fn config_loader(mut self, loader: ConfigLoader) -> Self {
+        self.config_loader = Some(loader);
+        self
+    }
+
+    /// Reload on SIGHUP, and when `config.json`, `frpc.toml`, `models.lock`, the certificates of
+    /// `[http]` or a file added with [`watch_file`](Self::watch_file) changes. Disabled by
+    /// default
+    pub fn hot_reload(mut self, enable: bool) -> Self {
+        self.hot_reload = enable;
+        self
+    }
+
+    /// Reload when the file changes, with hot reload enabled
+    pub fn watch_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.watched_files.push(path.into());
+        self
+    }
+
+    /// Address of LlamaEdge API Server instance, a socket address or an
+    /// [`ApiServerEndpoint`]. Defaults to `0.0.0.0:8080`
+    pub fn server_addr(mut self, addr: impl Into<ApiServerEndpoint>) -> Self {
+        self.config.server_socket_addr = addr.into();
+        self
+    }
+
+    /// Path to gaianet directory
+    pub fn gaianet_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.gaianet_dir = Some(dir.into());
+        self
+    }
+
+    /// Base URL of the hub. `{domain}` is replaced with the domain of the node. Defaults to
+    /// `https://hub.domain.{domain}`
+    pub fn hub_url(mut self, url: impl Into<String>) -> Self {
+        self.config.hub.url = url.into();
+        self
+    }
+
+    /// Log file of the API server. Required without a gaianet directory
+    pub fn server_log_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.server_log_file = Some(path.into());
+        self
+    }
+
+    /// Interval in seconds for checking the health and sending notifications. Defaults to 10
+    pub fn interval(mut self, secs: u64) -> Self {
+        self.config.interval = secs;
+        self
+    }
+
+    pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn rag_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.rag_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Add a URL to POST the server information to
+    pub fn info_subscriber(mut self, url: impl Into<String>) -> Self {
+        self.info_subscribers.push(url.into());
+        self
+    }
+
+    /// Add a URL to POST the server health to
+    pub fn health_subscriber(mut self, url: impl Into<String>) -> Self {
+        self.health_subscribers.push(url.into());
+        self
+    }
+
+    /// Source of time. Defaults to [`SystemClock`]
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Prober sending synthetic requests to the API server. Defaults to [`HttpProber`]
+    pub fn prober(mut self, prober: Arc<dyn Prober>) -> Self {
+        self.prober = Some(prober);
+        self
+    }
+
+    /// Write the payloads to the output instead of sending them to the subscribers,
+    /// validated against their JSON schema
+    pub fn dry_run(mut self, output: DryRunOutput) -> Self {
+        self.dry_run = Some(output);
+        self
+    }
+
+    /// Key signing the payloads. Defaults to the key of `nodeid.json` in the gaianet
+    /// directory, if any
+    pub fn identity(mut self, identity: NodeIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Sign the payloads sent to the subscriber at `url` with HMAC-SHA256, using the shared
+    /// `secret`. See [`crate::webhook`]
+    pub fn webhook_secret(mut self, url: impl Into<String>, secret: impl Into<String>) -> Self {
+        self.secrets.insert(url.into(), secret.into());
+        self
+    }
+
+    /// Encode the payloads sent to the subscriber at `url`, e.g. as CloudEvents. Defaults to
+    /// the raw JSON
+    pub fn payload_encoding(mut self, url: impl Into<String>, encoding: PayloadEncoding) -> Self {
+        self.encodings.insert(url.into(), encoding);
+        self
+    }
+
+    pub async fn build(self) -> Result<Assistant, AssistantError> {
+        let config = self.config;
+
+        let server_addr = config.server_socket_addr.clone();
+        info!("Address of API server: {}", &server_addr);
+
+        let mut info_subscribers = self.info_subscribers;
+        let mut health_subscribers = self.health_subscribers;
+        let mut watched_files = self.watched_files;
+        let mut server_log_file = self.server_log_file;
+        let mut hub_urls = None;
+        let mut node_config = None;
+        let mut models = Vec::new();
+        let mut hash_cache = None;
+        let mut identity = self.identity;
+        let mut secrets = self.secrets;
+        let mut encodings = self.encodings;
+        for subscriber in config.subscribers.iter() {
+            if subscriber.topics.contains(&Topic::Info) {
+                info_subscribers.push(subscriber.url.clone());
+            }
+            if subscriber.topics.contains(&Topic::Health) {
+                health_subscribers.push(subscriber.url.clone());
+            }
+            if let Some(secret) = &subscriber.secret {
+                secrets.insert(subscriber.url.clone(), secret.clone());
+            }
+            if subscriber.encoding != PayloadEncoding::Json {
+                encodings.insert(subscriber.url.clone(), subscriber.encoding);
+            }
+        }
+        check_secrets(
+            secrets
+                .iter()
+                .map(|(url, secret)| (url.as_str(), secret.as_str())),
+        )?;
+        let mut extras = InfoExtras {
+            system_prompt: self.system_prompt.clone().unwrap_or_default(),
+            rag_prompt: self.rag_prompt.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(gaianet_dir) = &self.gaianet_dir {
+            let node = NodeConfig::load(gaianet_dir, &config.paths).await?;
+
+            let urls = HubUrls::new(&config.hub, &node).map_err(|e| {
+                let err_msg = format!("Invalid hub URL {}", e);
+                error!("{}", &err_msg);
+                AssistantError::ConfigError(err_msg)
+            })?;
+            info_subscribers.extend(urls.info.iter().cloned());
+            health_subscribers.extend(urls.health.iter().cloned());
+            hub_urls = Some(urls);
+
+            // reuse the hashes of the unchanged models, the others are hashed by `run`
+            let cache_file = gaianet_dir.join(&config.paths.hash_cache);
+            let cache = HashCache::load(&cache_file);
+            let lock = ModelsLock::load(&gaianet_dir.join(&config.paths.models_lock))?;
+            models = node_models(gaianet_dir, &node.config, &lock);
+            for model in models.iter() {
+                extras.set_gguf(model.kind, load_metadata(&model.path).await);
+                if let Ok(fingerprint) = Fingerprint::of(&model.path) {
+                    if let Some(sha256) = cache.get(&model.path, &fingerprint) {
+                        info!("sha256 of {} model (cached): {}", model.kind, sha256);
+                        extras.set_sha256(model.kind, sha256.to_string());
+                    }
+                }
+            }
+            hash_cache = Some(cache_file);
+            if self.system_prompt.is_none() {
+                extras.system_prompt = node.config.system_prompt.clone();
+            }
+            if self.rag_prompt.is_none() {
+                extras.rag_prompt = node.config.rag_prompt.clone();
+            }
+
+            // decrypting the keystore takes a while with scrypt
+            let nodeid = gaianet_dir.join(&config.paths.nodeid);
+            if identity.is_none() && nodeid.exists() {
+                let loaded = tokio::task::spawn_blocking(move || NodeIdentity::load(&nodeid))
+                    .await
+                    .map_err(|e| AssistantError::Operation(e.to_string()))??;
+                identity = Some(loaded);
+            } else if identity.is_none() {
+                warn!(
+                    "Not found {}: the payloads are sent unsigned",
+                    nodeid.display()
+                );
+            }
+
+            server_log_file.get_or_insert(node.server_log_file.clone());
+            node_config = Some(node.clone());
+            watched_files.push(gaianet_dir.join("config.json"));
+            watched_files.push(gaianet_dir.join(&config.paths.frpc_toml));
+            watched_files.push(gaianet_dir.join(&config.paths.models_lock));
+            watched_files.push(gaianet_dir.join(&config.paths.api_key));
+        }
+
+        // the certificates are read again when they are rotated
+        watched_files.extend(
+            [
+                &config.http.ca_cert,
+                &config.http.client_cert,
+                &config.http.client_key,
+            ]
+            .into_iter()
+            .flatten()
+            .cloned(),
+        );
+
+        let server_log_file = match server_log_file {
+            Some(path) => path,
+            None => {
+                let err_msg = "Missing the log file of the API server.";
+                error!("{}", err_msg);
+                return Err(AssistantError::ArgumentError(err_msg.to_string()));
+            }
+        };
+
+        // schedules of the periodic tasks
+        let schedules = Schedules::new(&config);
+        info!("Schedules: {}", &schedules);
+
+        // add subscribers for server info
+        let mut server_info_subscribers = HashSet::new();
+        for url in info_subscribers {
+            info!("Add subscriber for server info: {}", &url);
+            server_info_subscribers.insert(url);
+        }
+
+        // add subscribers for server health
+        let mut server_health_subscribers = HashSet::new();
+        for url in health_subscribers {
+            info!("Add subscriber for server health: {}", &url);
+            server_health_subscribers.insert(url);
+        }
+
+        // shared by the requests to the subscribers
+        let client = http::client(&config.http)?;
+        let api_client: ApiClient =
+            Arc::new(RwLock::new(http::api_client(&config.http, &server_addr)?));
+        let api_key: ApiKey = Arc::new(RwLock::new(read_api_key(
+            &config,
+            self.gaianet_dir.as_deref(),
+        )?));
+
+        let probe: ProbeSettings = Arc::new(RwLock::new(config.probe.clone()));
+        let prober = match self.prober {
+            Some(prober) => prober,
+            None => Arc::new(HttpProber::with_settings(
+                server_addr.clone(),
+                Arc::clone(&probe),
+                Arc::clone(&api_client),
+                Arc::clone(&api_key),
+            )),
+        };
+
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        let clock = self.clock.unwrap_or_else(|| Arc::new(SystemClock));
+
+        let dry_run = match &self.dry_run {
+            Some(output) => Some(Arc::new(DryRun::new(output)?)),
+            None => None,
+        };
+
+        Ok(Assistant {
+            server_addr,
+            api_client,
+            api_key,
+            server_log_file: Arc::new(RwLock::new(server_log_file.to_string_lossy().to_string())),
+            gaianet_dir: self.gaianet_dir,
+            config: Arc::new(RwLock::new(config)),
+            config_loader: self.config_loader,
+            hot_reload: self.hot_reload,
+            watched_files,
+            schedules: Arc::new(RwLock::new(schedules)),
+            probe,
+            rearm: Arc::new(Notify::new()),
+            system_prompt: self.system_prompt,
+            rag_prompt: self.rag_prompt,
+            extras: Arc::new(RwLock::new(extras)),
+            models: Arc::new(RwLock::new(models)),
+            hash_cache,
+            hub_urls: Arc::new(RwLock::new(hub_urls)),
+            node: Arc::new(RwLock::new(node_config)),
+            info_subscribers: Arc::new(RwLock::new(server_info_subscribers)),
+            health_subscribers: Arc::new(RwLock::new(server_health_subscribers)),
+            server_info: Arc::new(RwLock::new(None)),
+            server_health: Arc::new(RwLock::new(None)),
+            issues: Arc::new(RwLock::new(Default::default())),
+            stats: Arc::new(RwLock::new(Default::default())),
+            clock: Arc::clone(&clock),
+            prober,
+            events,
+            shutdown: Shutdown::new(),
+            dry_run,
+            delivery: Arc::new(Delivery::new(client, identity, secrets, encodings, clock)),
+        })
+    }
+}
+
+// Result of a spawned task: its own error, or its panic
+async fn joined(
+    task: impl Future<Output = Result<Result<(), AssistantError>, JoinError>>,
+) -> Result<(), AssistantError> {
+    match task.await {
+        Ok(result) => result,
+        Err(e) => {
+            let err_msg = format!("A task of the assistant failed: {}", e);
+
+            error!("{}", &err_msg);
+
+            Err(AssistantError::Operation(err_msg))
+        }
+    }
+}
+
+// Reject the empty secrets of the subscribers, given by URL
+pub(crate) fn check_secrets<'a>(
+    mut secrets: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Result<(), AssistantError> {
+    match secrets.find(|(_, secret)| secret.is_empty()) {
+        Some((url, _)) => {
+            let err_msg = format!("The secret of the subscriber {} is empty", url);
+            error!("{}", &err_msg);
+            Err(AssistantError::ConfigError(err_msg))
+        }
+        None => Ok(()),
+    }
+}
+
+// API key of the API server, from the configuration or the gaianet directory
+pub(crate) fn read_api_key(
+    config: &AssistantConfig,
+    gaianet_dir: Option<&Path>,
+) -> Result<Option<String>, AssistantError> {
+    match gaianet_dir {
+        Some(gaianet_dir) => http::api_key(config, gaianet_dir),
+        None => Ok(config.api_key.clone()),
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{schedule::Task, SharedSchedules};
+use chrono::{DateTime, Utc};
+use log::info;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Source of time for the health checker and the notifier.
+pub trait Clock: Send + Sync {
+    /// Current wall-clock time
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Wait until `duration` has elapsed
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Clock backed by the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock whose wall time is derived from the tokio timer, starting at `origin`, so that it
+/// follows `tokio::time::pause` and `tokio::time::advance` in tests.
+#[derive(Debug, Clone, Copy)]
+pub struct TokioClock {
+    origin: DateTime<Utc>,
+    started: tokio::time::Instant,
+}
+impl TokioClock {
+    pub fn new(origin: DateTime<Utc>) -> Self {
+        Self {
+            origin,
+            started: tokio::time::Instant::now(),
+        }
+    }
+}
+impl Clock for TokioClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = tokio::time::Instant::now().duration_since(self.started);
+        self.origin + chrono::Duration::from_std(elapsed).unwrap()
+    }
+}
+
+/// Waits for the next run of a task, on a schedule that may change while waiting.
+///
+/// The runs follow each other at a fixed rate: the next run is due an interval after the
+/// previous one was due, however long the task took. Runs missed meanwhile are skipped.
+#[derive(Clone)]
+pub(crate) struct Ticker {
+    schedules: SharedSchedules,
+    task: Task,
+    rearm: Arc<Notify>,
+    clock: SharedClock,
+    // when the previous run was due, jitter excluded. None until the first run, or after a
+    // re-arm
+    last_due: Arc<Mutex<Option<DateTime<Utc>>>>,
+}
+impl Ticker {
+    pub(crate) fn new(
+        schedules: SharedSchedules,
+        task: Task,
+        rearm: Arc<Notify>,
+        clock: SharedClock,
+    ) -> Self {
+        Self {
+            schedules,
+            task,
+            rearm,
+            clock,
+            last_due: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Time of the next run after `after`, None if the task is off
+    pub(crate) async fn next(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedules.read().await.get(self.task).next(after)
+    }
+
+    /// Wait for the next run of the task. If the ticker is re-armed meanwhile, wait for the
+    /// schedule read at that moment instead. A task that is off waits for a re-arm only.
+    pub(crate) async fn wait(&self) {
+        loop {
+            // register before reading the schedule, so that a concurrent re-arm is not missed
+            let rearmed = self.rearm.notified();
+            tokio::pin!(rearmed);
+            rearmed.as_mut().enable();
+
+            let now = self.clock.now();
+            let schedule = self.schedules.read().await.get(self.task).clone();
+            let last_due = *self.last_due.lock().unwrap_or_else(|e| e.into_inner());
+            let due = match last_due
+                .filter(|due| *due <= now)
+                .and_then(|due| schedule.due(due))
+            {
+                Some(due) if due >= now => Some(due),
+                _ => schedule.due(now),
+            };
+            let sleep: Sleep = match due {
+                Some(due) => {
+                    let next = chrono::Duration::from_std(schedule.delay())
+                        .ok()
+                        .and_then(|delay| due.checked_add_signed(delay))
+                        .unwrap_or(due);
+                    self.clock
+                        .sleep((next - now).to_std().unwrap_or(Duration::ZERO))
+                }
+                None => Box::pin(std::future::pending()),
+            };
+            tokio::select! {
+                _ = sleep => {
+                    *self.last_due.lock().unwrap_or_else(|e| e.into_inner()) = due;
+                    return;
+                }
+                _ = rearmed => {
+                    info!("Re-arm the ticker");
+                    *self.last_due.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::AssistantConfig, schedule::Schedules};
+    use chrono::TimeZone;
+    use tokio::sync::RwLock;
+
+    fn every(interval: u64) -> Schedules {
+        Schedules::new(&AssistantConfig {
+            interval,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tokio_clock_follows_paused_time() {
+        let origin = Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap();
+        let clock = TokioClock::new(origin);
+        assert_eq!(clock.now(), origin);
+
+        clock.sleep(Duration::from_secs(3 * 3600)).await;
+        assert_eq!(clock.now(), origin + chrono::Duration::hours(3));
+
+        tokio::time::advance(Duration::from_millis(1500)).await;
+        assert_eq!(
+            clock.now(),
+            origin + chrono::Duration::hours(3) + chrono::Duration::milliseconds(1500)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticker_rearms_with_new_interval() {
+        let origin = Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap();
+        let clock = Arc::new(TokioClock::new(origin));
+        let schedules: SharedSchedules = Arc::new(RwLock::new(every(600)));
+        let rearm = Arc::new(Notify::new());
+        let ticker = Ticker::new(
+            Arc::clone(&schedules),
+            Task::LogScan,
+            Arc::clone(&rearm),
+            clock.clone(),
+        );
+
+        let waiting = tokio::spawn({
+            let ticker = ticker.clone();
+            async move { ticker.wait().await }
+        });
+
+        tokio::time::sleep(Duration::from_secs(100)).await;
+        *schedules.write().await = every(10);
+        rearm.notify_waiters();
+
+        waiting.await.unwrap();
+        assert_eq!(clock.now(), origin + chrono::Duration::seconds(110));
+
+        // without a re-arm, the whole interval is waited for
+        ticker.wait().await;
+        assert_eq!(clock.now(), origin + chrono::Duration::seconds(120));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticker_keeps_a_fixed_rate() {
+        let origin = Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap();
+        let clock = Arc::new(TokioClock::new(origin));
+        let schedules: SharedSchedules = Arc::new(RwLock::new(every(10)));
+        let ticker = Ticker::new(
+            schedules,
+            Task::HealthPush,
+            Arc::new(Notify::new()),
+            clock.clone(),
+        );
+
+        ticker.wait().await;
+        assert_eq!(clock.now(), origin + chrono::Duration::seconds(10));
+
+        // the time spent by the task does not delay the next run
+        clock.sleep(Duration::from_secs(3)).await;
+        ticker.wait().await;
+        assert_eq!(clock.now(), origin + chrono::Duration::seconds(20));
+
+        // runs missed by a slow task are skipped
+        clock.sleep(Duration::from_secs(25)).await;
+        ticker.wait().await;
+        assert_eq!(clock.now(), origin + chrono::Duration::seconds(55));
+        ticker.wait().await;
+        assert_eq!(clock.now(), origin + chrono::Duration::seconds(65));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticker_follows_cron_and_waits_for_rearm_when_off() {
+        let origin = Utc.with_ymd_and_hms(2024, 8, 1, 0, 7, 30).unwrap();
+        let clock = Arc::new(TokioClock::new(origin));
+        let mut config = AssistantConfig::default();
+        config.schedules.health_push = Some("*/15 * * * *".parse().unwrap());
+        let schedules: SharedSchedules = Arc::new(RwLock::new(Schedules::new(&config)));
+        let rearm = Arc::new(Notify::new());
+        let ticker = Ticker::new(
+            Arc::clone(&schedules),
+            Task::HealthPush,
+            Arc::clone(&rearm),
+            clock.clone(),
+        );
+
+        ticker.wait().await;
+        assert_eq!(clock.now(), origin + chrono::Duration::seconds(450));
+
+        config.schedules.health_push = Some("off".parse().unwrap());
+        *schedules.write().await = Schedules::new(&config);
+        let waiting = tokio::spawn({
+            let ticker = ticker.clone();
+            async move { ticker.wait().await }
+        });
+        tokio::time::sleep(Duration::from_secs(86400)).await;
+        assert!(!waiting.is_finished());
+
+        config.schedules.health_push = Some("1m".parse().unwrap());
+        *schedules.write().await = Schedules::new(&config);
+        rearm.notify_waiters();
+        waiting.await.unwrap();
+        assert_eq!(
+            clock.now(),
+            origin + chrono::Duration::seconds(450 + 86400 + 60)
+        );
+    }
+}
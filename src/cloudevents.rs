@@ -0,0 +1,214 @@
+//! CloudEvents 1.0 encoding of the payloads, in structured or binary mode.
+
+use crate::config::PayloadEncoding;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+const SPEC_VERSION: &str = "1.0";
+
+// source of the events without a device ID, i.e. without a gaianet directory
+const DEFAULT_SOURCE: &str = "gaias";
+
+// Types of the events carrying the payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EventType {
+    // the health changed, or is pushed for the last time on shutdown
+    HealthChanged,
+    // the health is pushed unchanged, periodically or as a heartbeat
+    HealthReported,
+    // the server information was retrieved again
+    InfoUpdated,
+    // the server information changed with a model file
+    ModelChanged,
+}
+impl EventType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            EventType::HealthChanged => "net.gaianet.node.health.changed",
+            EventType::HealthReported => "net.gaianet.node.health.reported",
+            EventType::InfoUpdated => "net.gaianet.node.info.updated",
+            EventType::ModelChanged => "net.gaianet.node.model.changed",
+        }
+    }
+}
+
+// Payload sent to the subscribers, with what the CloudEvents need to describe it
+#[derive(Debug, Clone)]
+pub(crate) struct Message<'a> {
+    pub(crate) event_type: EventType,
+    // device ID of the node, if known
+    pub(crate) source: Option<&'a str>,
+    // JSON payload
+    pub(crate) payload: &'a str,
+    // same for all the subscribers and retries, so that duplicates can be told apart
+    pub(crate) id: String,
+    pub(crate) time: DateTime<Utc>,
+}
+impl<'a> Message<'a> {
+    // New event with a new id, occurring at `time`
+    pub(crate) fn new(
+        event_type: EventType,
+        source: Option<&'a str>,
+        payload: &'a str,
+        time: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            event_type,
+            source,
+            payload,
+            id: new_id(),
+            time,
+        }
+    }
+}
+
+// Request to a subscriber: headers and body
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Encoded {
+    pub(crate) headers: Vec<(&'static str, String)>,
+    pub(crate) body: String,
+}
+
+// Encode the message as an event
+pub(crate) fn encode(encoding: PayloadEncoding, message: &Message) -> Encoded {
+    let source = message.source.unwrap_or(DEFAULT_SOURCE);
+    let time = message.time.to_rfc3339_opts(SecondsFormat::Millis, true);
+    match encoding {
+        PayloadEncoding::Json => Encoded {
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: message.payload.to_string(),
+        },
+        PayloadEncoding::CloudeventsStructured => {
+            let data: Value = serde_json::from_str(message.payload)
+                .unwrap_or_else(|_| Value::String(message.payload.to_string()));
+            let event = json!({
+                "specversion": SPEC_VERSION,
+                "id": message.id,
+                "source": source,
+                "type": message.event_type.as_str(),
+                "time": time,
+                "datacontenttype": "application/json",
+                "data": data,
+            });
+            Encoded {
+                headers: vec![("Content-Type", "application/cloudevents+json".to_string())],
+                body: event.to_string(),
+            }
+        }
+        PayloadEncoding::CloudeventsBinary => Encoded {
+            headers: vec![
+                ("Content-Type", "application/json".to_string()),
+                ("ce-specversion", SPEC_VERSION.to_string()),
+                ("ce-id", message.id.clone()),
+                ("ce-source", source.to_string()),
+                ("ce-type", message.event_type.as_str().to_string()),
+                ("ce-time", time),
+            ],
+            body: message.payload.to_string(),
+        },
+    }
+}
+
+// random id, unique for the source
+fn new_id() -> String {
+    let mut bytes = [0u8; 16];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        // unique enough within a process
+        let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        bytes = (nanos as u128).to_be_bytes();
+    }
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn health_message() -> Message<'static> {
+        let time = Utc.with_ymd_and_hms(2024, 8, 1, 12, 0, 0).unwrap();
+        Message::new(
+            EventType::HealthChanged,
+            Some("0x1234"),
+            r#"{"health":true}"#,
+            time,
+        )
+    }
+
+    fn encode_health(encoding: PayloadEncoding) -> Encoded {
+        encode(encoding, &health_message())
+    }
+
+    #[test]
+    fn json_is_sent_as_is() {
+        let encoded = encode_health(PayloadEncoding::Json);
+
+        assert_eq!(encoded.body, r#"{"health":true}"#);
+        assert_eq!(
+            encoded.headers,
+            [("Content-Type", "application/json".to_string())]
+        );
+    }
+
+    #[test]
+    fn structured_mode_wraps_the_payload() {
+        let encoded = encode_health(PayloadEncoding::CloudeventsStructured);
+
+        let mut event: Value = serde_json::from_str(&encoded.body).unwrap();
+        assert_eq!(event["id"].as_str().unwrap().len(), 32);
+        event["id"] = json!("id");
+        assert_eq!(
+            event,
+            json!({
+                "specversion": "1.0",
+                "id": "id",
+                "source": "0x1234",
+                "type": "net.gaianet.node.health.changed",
+                "time": "2024-08-01T12:00:00.000Z",
+                "datacontenttype": "application/json",
+                "data": { "health": true }
+            })
+        );
+        assert_eq!(
+            encoded.headers,
+            [("Content-Type", "application/cloudevents+json".to_string())]
+        );
+    }
+
+    #[test]
+    fn binary_mode_sends_attributes_as_headers() {
+        let encoded = encode_health(PayloadEncoding::CloudeventsBinary);
+
+        assert_eq!(encoded.body, r#"{"health":true}"#);
+        let header = |name| {
+            encoded
+                .headers
+                .iter()
+                .find(|(header, _)| *header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(header("Content-Type"), Some("application/json"));
+        assert_eq!(header("ce-specversion"), Some("1.0"));
+        assert_eq!(header("ce-source"), Some("0x1234"));
+        assert_eq!(header("ce-type"), Some("net.gaianet.node.health.changed"));
+        assert_eq!(header("ce-time"), Some("2024-08-01T12:00:00.000Z"));
+
+        // each event has its own id
+        assert_eq!(header("ce-id").unwrap().len(), 32);
+        assert_ne!(header("ce-id"), Some(health_message().id.as_str()));
+    }
+
+    #[test]
+    fn message_keeps_its_id_and_time() {
+        let message = health_message();
+        let ids: Vec<_> = (0..3)
+            .map(|_| {
+                let encoded = encode(PayloadEncoding::CloudeventsStructured, &message);
+                let event: Value = serde_json::from_str(&encoded.body).unwrap();
+                assert_eq!(event["time"], "2024-08-01T12:00:00.000Z");
+                event["id"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert!(ids.iter().all(|id| *id == message.id), "{:?}", ids);
+    }
+}
@@ -0,0 +1,663 @@
+//! Configuration of the assistant.
+//!
+//! Settings are resolved in the following order, the first one found wins:
+//!
+//! 1. command line options
+//! 2. `GAIAS_*` environment variables
+//! 3. the configuration file, `assistant.toml` in the gaianet directory by default
+//! 4. built-in defaults
+
+use crate::{
+    endpoint::ApiServerEndpoint, error::AssistantError, schedule::Schedule, DEFAULT_INTERVAL,
+    DEFAULT_SERVER_SOCKET_ADDRESS, MAX_TIME_SPAN_IN_SECONDS,
+};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Name of the configuration file looked up in the gaianet directory
+pub const CONFIG_FILE_NAME: &str = "assistant.toml";
+/// Prefix of the environment variables overriding the configuration
+pub const ENV_PREFIX: &str = "GAIAS_";
+
+/// Configuration of the assistant.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssistantConfig {
+    /// Address of LlamaEdge API Server instance: `host:port`, `https://host:port` or
+    /// `unix:/path/to/socket`
+    pub server_socket_addr: ApiServerEndpoint,
+    /// API key of the API server, sent as a bearer token. Read from `paths.api_key` if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Interval in seconds for checking the health and sending notifications
+    pub interval: u64,
+    /// Log file of the assistant
+    pub log: PathBuf,
+    pub paths: PathsConfig,
+    pub hub: HubConfig,
+    pub probe: ProbeConfig,
+    pub models: ModelsConfig,
+    pub http: HttpConfig,
+    pub schedules: SchedulesConfig,
+    pub notifications: NotificationsConfig,
+    /// Subscribers besides the hub
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subscribers: Vec<SubscriberConfig>,
+}
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        Self {
+            server_socket_addr: DEFAULT_SERVER_SOCKET_ADDRESS.parse().unwrap(),
+            api_key: None,
+            interval: DEFAULT_INTERVAL,
+            log: PathBuf::from("assistant.log"),
+            paths: PathsConfig::default(),
+            hub: HubConfig::default(),
+            probe: ProbeConfig::default(),
+            models: ModelsConfig::default(),
+            http: HttpConfig::default(),
+            schedules: SchedulesConfig::default(),
+            notifications: NotificationsConfig::default(),
+            subscribers: Vec::new(),
+        }
+    }
+}
+impl AssistantConfig {
+    /// Parse a configuration file. Settings missing from the file take their default values.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AssistantError> {
+        let path = path.as_ref();
+
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            let err_msg = format!("Failed to read {}: {}", path.display(), e);
+            error!("{}", &err_msg);
+            AssistantError::ConfigError(err_msg)
+        })?;
+
+        toml::from_str(&content).map_err(|e| {
+            let err_msg = format!("Failed to parse {}: {}", path.display(), e);
+            error!("{}", &err_msg);
+            AssistantError::ConfigError(err_msg)
+        })
+    }
+
+    /// Path of the configuration file: `config_file`, or `GAIAS_CONFIG`, or `assistant.toml`
+    /// in the gaianet directory. Returns whether the file was given explicitly.
+    pub fn file_path(config_file: Option<&Path>, gaianet_dir: impl AsRef<Path>) -> (PathBuf, bool) {
+        match config_file
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(format!("{}CONFIG", ENV_PREFIX)).map(PathBuf::from))
+        {
+            Some(path) => (path, true),
+            None => (gaianet_dir.as_ref().join(CONFIG_FILE_NAME), false),
+        }
+    }
+
+    /// Load the configuration file, if any, and apply the `GAIAS_*` environment variables.
+    ///
+    /// An explicit configuration file, see [`file_path`](Self::file_path), must exist. The
+    /// default one is optional.
+    pub fn load(
+        config_file: Option<&Path>,
+        gaianet_dir: impl AsRef<Path>,
+    ) -> Result<Self, AssistantError> {
+        let mut config = match Self::file_path(config_file, gaianet_dir) {
+            (path, true) => Self::from_file(path)?,
+            (path, false) => match path.exists() {
+                true => Self::from_file(path)?,
+                false => Self::default(),
+            },
+        };
+
+        config.apply_env(|name| std::env::var(name).ok())?;
+
+        Ok(config)
+    }
+
+    /// Override settings with the `GAIAS_*` variables returned by `var`.
+    pub fn apply_env<F>(&mut self, var: F) -> Result<(), AssistantError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |name: &str| var(&format!("{}{}", ENV_PREFIX, name));
+
+        override_with(&mut self.server_socket_addr, "SERVER_SOCKET_ADDR", var)?;
+        override_option(&mut self.api_key, "API_KEY", var)?;
+        override_with(&mut self.interval, "INTERVAL", var)?;
+        override_with(&mut self.log, "LOG", var)?;
+        override_with(&mut self.paths.server_log, "SERVER_LOG", var)?;
+        override_with(&mut self.paths.frpc_toml, "FRPC_TOML", var)?;
+        override_with(&mut self.paths.hash_cache, "HASH_CACHE", var)?;
+        override_with(&mut self.paths.models_lock, "MODELS_LOCK", var)?;
+        override_with(&mut self.paths.nodeid, "NODEID", var)?;
+        override_with(&mut self.paths.api_key, "API_KEY_FILE", var)?;
+        override_with(&mut self.hub.url, "HUB_URL", var)?;
+        override_with(&mut self.hub.allow_http, "HUB_ALLOW_HTTP", var)?;
+        override_with(&mut self.hub.retries, "HUB_RETRIES", var)?;
+        override_with(&mut self.probe.prompt, "PROBE_PROMPT", var)?;
+        override_with(&mut self.probe.model, "PROBE_MODEL", var)?;
+        override_with(&mut self.probe.max_time_span, "PROBE_MAX_TIME_SPAN", var)?;
+        override_with(
+            &mut self.models.verify_interval,
+            "MODELS_VERIFY_INTERVAL",
+            var,
+        )?;
+        override_option(&mut self.http.ca_cert, "HTTP_CA_CERT", var)?;
+        override_option(&mut self.http.client_cert, "HTTP_CLIENT_CERT", var)?;
+        override_option(&mut self.http.client_key, "HTTP_CLIENT_KEY", var)?;
+        override_option(&mut self.http.proxy, "HTTP_PROXY", var)?;
+        override_with(&mut self.http.no_proxy, "HTTP_NO_PROXY", var)?;
+        override_with(&mut self.http.connect_timeout, "HTTP_CONNECT_TIMEOUT", var)?;
+        override_with(&mut self.http.timeout, "HTTP_TIMEOUT", var)?;
+        override_option(&mut self.schedules.log_scan, "SCHEDULE_LOG_SCAN", var)?;
+        override_option(&mut self.schedules.probe, "SCHEDULE_PROBE", var)?;
+        override_option(&mut self.schedules.health_push, "SCHEDULE_HEALTH_PUSH", var)?;
+        override_option(
+            &mut self.schedules.info_refresh,
+            "SCHEDULE_INFO_REFRESH",
+            var,
+        )?;
+        override_option(
+            &mut self.schedules.model_rehash,
+            "SCHEDULE_MODEL_REHASH",
+            var,
+        )?;
+        override_option(&mut self.schedules.heartbeat, "SCHEDULE_HEARTBEAT", var)?;
+        override_with(&mut self.notifications.mode, "NOTIFICATIONS_MODE", var)?;
+        override_with(&mut self.notifications.schema, "NOTIFICATIONS_SCHEMA", var)?;
+
+        Ok(())
+    }
+
+    /// Render the configuration in the format of the configuration file, with the API key
+    /// and the webhook secrets redacted.
+    pub fn to_toml(&self) -> Result<String, AssistantError> {
+        let mut config = self.clone();
+        if let Some(api_key) = config.api_key.as_mut() {
+            *api_key = REDACTED.to_string();
+        }
+        for subscriber in config.subscribers.iter_mut() {
+            if let Some(secret) = subscriber.secret.as_mut() {
+                *secret = REDACTED.to_string();
+            }
+        }
+        toml::to_string_pretty(&config).map_err(|e| {
+            AssistantError::ConfigError(format!("Failed to serialize the configuration: {}", e))
+        })
+    }
+}
+
+// rendered in place of the secrets
+const REDACTED: &str = "<redacted>";
+
+fn override_with<T, F>(field: &mut T, name: &str, var: F) -> Result<(), AssistantError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(value) = var(name) {
+        *field = parse_var(name, &value)?;
+    }
+
+    Ok(())
+}
+
+// Same as `override_with`, for settings that are unset by default
+fn override_option<T, F>(field: &mut Option<T>, name: &str, var: F) -> Result<(), AssistantError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(value) = var(name) {
+        *field = Some(parse_var(name, &value)?);
+    }
+
+    Ok(())
+}
+
+fn parse_var<T>(name: &str, value: &str) -> Result<T, AssistantError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|e| {
+        let err_msg = format!("Invalid value of {}{}: {}", ENV_PREFIX, name, e);
+        error!("{}", &err_msg);
+        AssistantError::ConfigError(err_msg)
+    })
+}
+
+/// Locations of the node files, relative to the gaianet directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// Log file of the API server
+    pub server_log: PathBuf,
+    /// frpc.toml holding the device ID
+    pub frpc_toml: PathBuf,
+    /// Cache of the sha256 of the model files
+    pub hash_cache: PathBuf,
+    /// Expected sha256 of the model files, by model URL
+    pub models_lock: PathBuf,
+    /// nodeid.json giving the keystore of the key signing the payloads
+    pub nodeid: PathBuf,
+    /// File holding the API key of the API server, if any
+    pub api_key: PathBuf,
+}
+impl Default for PathsConfig {
+    fn default() -> Self {
+        Self {
+            server_log: PathBuf::from("log/start-llamaedge.log"),
+            frpc_toml: PathBuf::from("gaia-frp/frpc.toml"),
+            hash_cache: PathBuf::from("assistant-hashes.json"),
+            models_lock: PathBuf::from("models.lock"),
+            nodeid: PathBuf::from("nodeid.json"),
+            api_key: PathBuf::from("api-key"),
+        }
+    }
+}
+
+/// Hub receiving the server information and health.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HubConfig {
+    /// URL template of the hub. `{domain}`, `{device_id}` and `{topic}` (`info` or `health`)
+    /// are replaced; without `{topic}`, `/device-{topic}/{device_id}` is appended
+    pub url: String,
+    /// URL templates of other hubs receiving the same payloads
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_urls: Vec<String>,
+    /// Allow plain `http://` hub URLs on hosts other than loopback, for local development
+    pub allow_http: bool,
+    /// Number of attempts to push the server information
+    pub retries: u32,
+}
+impl Default for HubConfig {
+    fn default() -> Self {
+        Self {
+            url: "https://hub.domain.{domain}".to_string(),
+            extra_urls: Vec::new(),
+            allow_http: false,
+            retries: 3,
+        }
+    }
+}
+
+/// Synthetic request sent to the API server when its log shows no recent responses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProbeConfig {
+    /// Content of the user message
+    pub prompt: String,
+    /// Name of the model in the request
+    pub model: String,
+    /// Seconds without logged responses before the API server is pinged
+    pub max_time_span: i64,
+}
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            prompt: "Who are you? <server-health>".to_string(),
+            model: "Phi-3-mini-4k-instruct".to_string(),
+            max_time_span: MAX_TIME_SPAN_IN_SECONDS,
+        }
+    }
+}
+
+/// Verification of the model files against their expected sha256.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelsConfig {
+    /// Seconds between two verifications of the model files, hashed from scratch. 0 disables
+    /// the verification besides startup and file changes
+    pub verify_interval: u64,
+}
+impl Default for ModelsConfig {
+    fn default() -> Self {
+        Self {
+            verify_interval: 24 * 60 * 60,
+        }
+    }
+}
+
+/// Schedules of the periodic tasks, see [`Schedule`]. Unset schedules keep the behavior of
+/// the older settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulesConfig {
+    /// Scan of the API server log. Every `interval` seconds if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_scan: Option<Schedule>,
+    /// Synthetic request, sent when the log shows no responses since the previous run. Every
+    /// `probe.max_time_span` seconds if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe: Option<Schedule>,
+    /// Push of the server health. Every `interval` seconds if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_push: Option<Schedule>,
+    /// Push of the server information, besides startup and changes. Off if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info_refresh: Option<Schedule>,
+    /// Verification of the model files, hashed from scratch. Every
+    /// `models.verify_interval` seconds if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_rehash: Option<Schedule>,
+    /// Push of the unchanged server health, with `notifications.mode = "on_change"`. Every 5
+    /// minutes if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<Schedule>,
+}
+
+/// How the server health is pushed to the hub and the subscribers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsConfig {
+    pub mode: NotificationMode,
+    pub schema: HealthSchema,
+}
+
+/// When the server health is pushed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationMode {
+    /// On the `health_push` schedule, changed or not
+    #[default]
+    Periodic,
+    /// As soon as the health changes, and otherwise on the `heartbeat` schedule. The
+    /// messages carry a sequence number and the seconds since the last change
+    OnChange,
+}
+impl FromStr for NotificationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "periodic" => Ok(NotificationMode::Periodic),
+            "on_change" => Ok(NotificationMode::OnChange),
+            _ => Err(format!("{}, expected periodic or on_change", s)),
+        }
+    }
+}
+
+/// Shape of the health payloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthSchema {
+    /// `{"health": true}`, understood by all hubs
+    #[default]
+    Legacy,
+    /// Versioned payload of `schemas/device-health.schema.json`, with the device, the
+    /// request stats and the uptimes
+    V2,
+}
+impl FromStr for HealthSchema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(HealthSchema::Legacy),
+            "v2" => Ok(HealthSchema::V2),
+            _ => Err(format!("{}, expected legacy or v2", s)),
+        }
+    }
+}
+
+/// HTTP client of the requests to the API server, the hub and the subscribers. Rebuilt on
+/// reload, with the certificate and key files read again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// PEM bundle of the CA certificates trusted besides the built-in roots
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+    /// PEM certificate chain presented to the servers requiring client authentication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key of the client certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+    /// URL of the proxy: `http://`, `https://`, `socks5://` or `socks5h://`. Without, the
+    /// proxy of the `HTTPS_PROXY` and `HTTP_PROXY` variables is used, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Comma-separated hosts, domains and IP ranges reached without the proxy
+    pub no_proxy: String,
+    /// Seconds to establish a connection. 0 to wait indefinitely
+    pub connect_timeout: u64,
+    /// Seconds to complete a request, including the chat probe. 0 to wait indefinitely
+    pub timeout: u64,
+}
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            proxy: None,
+            no_proxy: "localhost,127.0.0.1,::1".to_string(),
+            connect_timeout: 10,
+            timeout: 60,
+        }
+    }
+}
+
+/// Subscriber besides the hub, such as an internal service receiving the node events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriberConfig {
+    /// URL the payloads are POSTed to
+    pub url: String,
+    /// Payloads sent to the subscriber. Defaults to all
+    #[serde(default = "Topic::all")]
+    pub topics: Vec<Topic>,
+    /// Shared secret signing the payloads with HMAC-SHA256, see [`crate::webhook`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// Encoding of the payloads. Defaults to the raw JSON sent to the hub
+    #[serde(default)]
+    pub encoding: PayloadEncoding,
+}
+
+/// How the payloads are encoded in the requests to a subscriber.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadEncoding {
+    /// The payload as the body, as sent to the hub
+    #[default]
+    Json,
+    /// CloudEvents 1.0 structured mode: the event and its payload as the JSON body
+    CloudeventsStructured,
+    /// CloudEvents 1.0 binary mode: the event attributes as `ce-*` headers, the payload as
+    /// the body
+    CloudeventsBinary,
+}
+
+/// Payload sent to the subscribers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    /// Server information
+    Info,
+    /// Server health
+    Health,
+}
+impl Topic {
+    fn all() -> Vec<Topic> {
+        vec![Topic::Info, Topic::Health]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn missing_settings_take_defaults() {
+        let config: AssistantConfig = toml::from_str(
+            r#"
+            interval = 30
+
+            [probe]
+            model = "Llama-3-8B"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.interval, 30);
+        assert_eq!(config.probe.model, "Llama-3-8B");
+        assert_eq!(config.probe.prompt, ProbeConfig::default().prompt);
+        assert_eq!(config.hub, HubConfig::default());
+        assert_eq!(config.server_socket_addr.to_string(), "0.0.0.0:8080");
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        let result = toml::from_str::<AssistantConfig>("[hub]\nurl = \"x\"\nretry = 5\n");
+
+        assert!(result.unwrap_err().to_string().contains("retry"));
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let mut config: AssistantConfig =
+            toml::from_str("interval = 30\n[hub]\nretries = 5\n").unwrap();
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("GAIAS_INTERVAL", "5"),
+            ("GAIAS_HUB_URL", "http://localhost:3000"),
+            ("GAIAS_HUB_ALLOW_HTTP", "true"),
+            ("GAIAS_PROBE_MAX_TIME_SPAN", "120"),
+            ("GAIAS_HTTP_PROXY", "socks5h://proxy.internal:1080"),
+        ]);
+
+        config
+            .apply_env(|name| env.get(name).map(|value| value.to_string()))
+            .unwrap();
+
+        assert_eq!(config.interval, 5);
+        assert_eq!(config.hub.url, "http://localhost:3000");
+        assert!(config.hub.allow_http);
+        assert_eq!(config.hub.retries, 5);
+        assert_eq!(config.probe.max_time_span, 120);
+        assert_eq!(
+            config.http.proxy.as_deref(),
+            Some("socks5h://proxy.internal:1080")
+        );
+        assert_eq!(config.http.ca_cert, None);
+    }
+
+    #[test]
+    fn invalid_env_value_names_variable() {
+        let mut config = AssistantConfig::default();
+
+        let err = config
+            .apply_env(|name| (name == "GAIAS_SERVER_SOCKET_ADDR").then(|| "localhost".to_string()))
+            .unwrap_err();
+
+        assert!(err.to_string().contains("GAIAS_SERVER_SOCKET_ADDR"));
+    }
+
+    #[test]
+    fn schedules_are_read_from_file_and_env() {
+        let mut config: AssistantConfig = toml::from_str(
+            r#"
+            [schedules]
+            health_push = "1m ~15s"
+            info_refresh = "0 */6 * * *"
+            "#,
+        )
+        .unwrap();
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("GAIAS_SCHEDULE_PROBE", "off"),
+            ("GAIAS_NOTIFICATIONS_MODE", "on_change"),
+        ]);
+
+        config
+            .apply_env(|name| env.get(name).map(|value| value.to_string()))
+            .unwrap();
+
+        let schedules = &config.schedules;
+        assert_eq!(
+            schedules.health_push.as_ref().unwrap().to_string(),
+            "1m ~15s"
+        );
+        assert_eq!(
+            schedules.info_refresh.as_ref().unwrap().to_string(),
+            "0 */6 * * *"
+        );
+        assert!(schedules.probe.as_ref().unwrap().is_off());
+        assert_eq!(schedules.log_scan, None);
+        assert_eq!(config.notifications.mode, NotificationMode::OnChange);
+
+        let err =
+            toml::from_str::<AssistantConfig>("[schedules]\nprobe = \"5 minutes\"\n").unwrap_err();
+        assert!(err.to_string().contains("5 minutes"), "{}", err);
+    }
+
+    #[test]
+    fn subscribers_take_all_topics_by_default() {
+        let config: AssistantConfig = toml::from_str(
+            r#"
+            [[subscribers]]
+            url = "https://events.internal/gaianet"
+            secret = "whsec_0123456789"
+
+            [[subscribers]]
+            url = "https://status.internal/gaianet"
+            topics = ["health"]
+            encoding = "cloudevents_structured"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.subscribers[0].topics, [Topic::Info, Topic::Health]);
+        assert_eq!(
+            config.subscribers[0].secret.as_deref(),
+            Some("whsec_0123456789")
+        );
+        assert_eq!(config.subscribers[1].topics, [Topic::Health]);
+        assert_eq!(config.subscribers[1].secret, None);
+        assert_eq!(config.subscribers[0].encoding, PayloadEncoding::Json);
+        assert_eq!(
+            config.subscribers[1].encoding,
+            PayloadEncoding::CloudeventsStructured
+        );
+    }
+
+    #[test]
+    fn rendered_config_round_trips() {
+        let mut config = AssistantConfig::default();
+        config.probe.prompt = "ping".to_string();
+        config.api_key = Some("gaia-0123456789".to_string());
+        config.schedules.health_push = Some("*/5 * * * * ~1m".parse().unwrap());
+        config.subscribers.push(SubscriberConfig {
+            url: "https://events.internal/gaianet".to_string(),
+            topics: vec![Topic::Info],
+            secret: Some("whsec_0123456789".to_string()),
+            encoding: PayloadEncoding::CloudeventsBinary,
+        });
+
+        let serialized = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(
+            toml::from_str::<AssistantConfig>(&serialized).unwrap(),
+            config
+        );
+
+        // the secrets are redacted when rendered
+        let rendered = config.to_toml().unwrap();
+        assert!(!rendered.contains("whsec_0123456789"), "{}", rendered);
+        assert!(!rendered.contains("gaia-0123456789"), "{}", rendered);
+        let mut redacted = config.clone();
+        redacted.api_key = Some("<redacted>".to_string());
+        redacted.subscribers[0].secret = Some("<redacted>".to_string());
+        assert_eq!(
+            toml::from_str::<AssistantConfig>(&rendered).unwrap(),
+            redacted
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::gaianet::GaianetConfig;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Inconsistency between the server information and `config.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Mismatch {
+    ChatModelName,
+    EmbeddingModelName,
+    EmbeddingModelMissing,
+    ChatCtxSize,
+    EmbeddingCtxSize,
+    PromptTemplate,
+}
+impl Mismatch {
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            Mismatch::ChatModelName => "chat_model_name_mismatch",
+            Mismatch::EmbeddingModelName => "embedding_model_name_mismatch",
+            Mismatch::EmbeddingModelMissing => "embedding_model_missing",
+            Mismatch::ChatCtxSize => "chat_ctx_size_mismatch",
+            Mismatch::EmbeddingCtxSize => "embedding_ctx_size_mismatch",
+            Mismatch::PromptTemplate => "prompt_template_mismatch",
+        }
+    }
+}
+
+/// Mismatch reported in the `warnings` of the server information.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Warning {
+    #[serde(skip)]
+    pub(crate) mismatch: Mismatch,
+    pub(crate) reason: &'static str,
+    pub(crate) message: String,
+}
+impl Warning {
+    fn new(mismatch: Mismatch, message: String) -> Self {
+        Self {
+            mismatch,
+            reason: mismatch.reason(),
+            message,
+        }
+    }
+}
+
+// Compare the server information retrieved from `/v1/info` with `config.json`
+pub(crate) fn check(server_info: &Value, config: &GaianetConfig) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let chat_model = &server_info["chat_model"];
+    let embedding_model = &server_info["embedding_model"];
+
+    for (model, url, mismatch) in [
+        (chat_model, &config.chat_url, Mismatch::ChatModelName),
+        (
+            embedding_model,
+            &config.embedding_url,
+            Mismatch::EmbeddingModelName,
+        ),
+    ] {
+        if let (Some(name), Some(file)) = (model["name"].as_str(), file_name(url)) {
+            if !file.to_lowercase().contains(&name.to_lowercase()) {
+                warnings.push(Warning::new(
+                    mismatch,
+                    format!(
+                        "The model {} reported by the API server does not match the model file {} in config.json",
+                        name, file
+                    ),
+                ));
+            }
+        }
+    }
+
+    if server_info["api_server"]["type"] == "rag" {
+        if !embedding_model.is_object() {
+            warnings.push(Warning::new(
+                Mismatch::EmbeddingModelMissing,
+                "The RAG API server reports no embedding model".to_string(),
+            ));
+        } else if config.embedding_url.is_empty() {
+            warnings.push(Warning::new(
+                Mismatch::EmbeddingModelMissing,
+                "The RAG API server reports an embedding model missing from config.json"
+                    .to_string(),
+            ));
+        }
+    }
+
+    for (model, expected, mismatch, field) in [
+        (
+            chat_model,
+            config.chat_ctx_size,
+            Mismatch::ChatCtxSize,
+            "chat_ctx_size",
+        ),
+        (
+            embedding_model,
+            config.embedding_ctx_size,
+            Mismatch::EmbeddingCtxSize,
+            "embedding_ctx_size",
+        ),
+    ] {
+        if let (Some(reported), Some(expected)) = (model["ctx_size"].as_u64(), expected) {
+            if reported != expected {
+                warnings.push(Warning::new(
+                    mismatch,
+                    format!(
+                        "The context size {} reported by the API server differs from {} {} in config.json",
+                        reported, field, expected
+                    ),
+                ));
+            }
+        }
+    }
+
+    if let Some(reported) = chat_model["prompt_template"].as_str() {
+        if !config.prompt_template.is_empty()
+            && normalize(reported) != normalize(&config.prompt_template)
+        {
+            warnings.push(Warning::new(
+                Mismatch::PromptTemplate,
+                format!(
+                    "The prompt template {} reported by the API server differs from {} in config.json",
+                    reported, config.prompt_template
+                ),
+            ));
+        }
+    }
+
+    warnings
+}
+
+fn file_name(url: &str) -> Option<&str> {
+    url.split('/').next_back().filter(|name| !name.is_empty())
+}
+
+// `llama-3-chat` in config.json is reported as `Llama3Chat`
+fn normalize(template: &str) -> String {
+    template
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn node() -> GaianetConfig {
+        GaianetConfig {
+            domain: "gaia.domains".to_string(),
+            chat_url: "https://example.com/Meta-Llama-3-8B-Instruct-Q5_K_M.gguf".to_string(),
+            embedding_url: "https://example.com/nomic-embed-text-v1.5.f16.gguf".to_string(),
+            chat_ctx_size: Some(16384),
+            embedding_ctx_size: Some(8192),
+            prompt_template: "llama-3-chat".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn server_info() -> Value {
+        json!({
+            "api_server": { "type": "rag" },
+            "chat_model": {
+                "name": "Llama-3-8B-Instruct",
+                "ctx_size": 16384,
+                "prompt_template": "Llama3Chat"
+            },
+            "embedding_model": { "name": "nomic-embed-text-v1.5", "ctx_size": 8192 }
+        })
+    }
+
+    fn reasons(warnings: Vec<Warning>) -> Vec<&'static str> {
+        warnings.into_iter().map(|warning| warning.reason).collect()
+    }
+
+    #[test]
+    fn consistent_settings_have_no_warnings() {
+        assert_eq!(check(&server_info(), &node()), vec![]);
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let mut info = server_info();
+        info["chat_model"]["name"] = "Phi-3-mini-4k-instruct".into();
+        info["chat_model"]["ctx_size"] = 4096.into();
+        info["chat_model"]["prompt_template"] = "Phi3Chat".into();
+
+        assert_eq!(
+            reasons(check(&info, &node())),
+            [
+                "chat_model_name_mismatch",
+                "chat_ctx_size_mismatch",
+                "prompt_template_mismatch"
+            ]
+        );
+    }
+
+    #[test]
+    fn rag_server_needs_embedding_model() {
+        let mut info = server_info();
+        info.as_object_mut().unwrap().remove("embedding_model");
+        assert_eq!(reasons(check(&info, &node())), ["embedding_model_missing"]);
+
+        let mut node = node();
+        node.embedding_url = String::new();
+        assert_eq!(
+            reasons(check(&server_info(), &node)),
+            ["embedding_model_missing"]
+        );
+
+        // a chat server needs none
+        info["api_server"]["type"] = "chat".into();
+        assert_eq!(check(&info, &node), vec![]);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    clock::SharedClock,
+    cloudevents::{self, EventType, Message},
+    config::{PayloadEncoding, SubscriberConfig},
+    error::AssistantError,
+    identity::NodeIdentity,
+    webhook,
+};
+use std::{collections::HashMap, sync::RwLock};
+
+// How the payloads are sent to the subscribers
+pub(crate) struct Delivery {
+    // rebuilt when the `[http]` settings are reloaded
+    client: RwLock<reqwest::Client>,
+    // key of the node, signing the payloads sent to all subscribers
+    identity: Option<NodeIdentity>,
+    // shared secrets of the webhook signatures, by subscriber URL, updated on reload
+    secrets: RwLock<HashMap<String, String>>,
+    // encodings other than the raw JSON, by subscriber URL, updated on reload
+    encodings: RwLock<HashMap<String, PayloadEncoding>>,
+    // time of the events and of the webhook signatures
+    clock: SharedClock,
+}
+impl Delivery {
+    pub(crate) fn new(
+        client: reqwest::Client,
+        identity: Option<NodeIdentity>,
+        secrets: HashMap<String, String>,
+        encodings: HashMap<String, PayloadEncoding>,
+        clock: SharedClock,
+    ) -> Self {
+        Self {
+            client: RwLock::new(client),
+            identity,
+            secrets: RwLock::new(secrets),
+            encodings: RwLock::new(encodings),
+            clock,
+        }
+    }
+
+    // Send the next requests with `client`
+    pub(crate) fn set_client(&self, client: reqwest::Client) {
+        *self.client.write().unwrap_or_else(|e| e.into_inner()) = client;
+    }
+
+    // Forget the secrets and encodings of the `removed` subscribers, then apply those of the
+    // `added` ones
+    pub(crate) fn update_subscribers(
+        &self,
+        removed: &[&SubscriberConfig],
+        added: &[&SubscriberConfig],
+    ) {
+        let mut secrets = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        let mut encodings = self.encodings.write().unwrap_or_else(|e| e.into_inner());
+        for subscriber in removed {
+            secrets.remove(&subscriber.url);
+            encodings.remove(&subscriber.url);
+        }
+        for subscriber in added {
+            if let Some(secret) = &subscriber.secret {
+                secrets.insert(subscriber.url.clone(), secret.clone());
+            }
+            if subscriber.encoding != PayloadEncoding::Json {
+                encodings.insert(subscriber.url.clone(), subscriber.encoding);
+            }
+        }
+    }
+
+    // New message to send to the subscribers, occurring now
+    pub(crate) fn message<'a>(
+        &self,
+        event_type: EventType,
+        source: Option<&'a str>,
+        payload: &'a str,
+    ) -> Message<'a> {
+        Message::new(event_type, source, payload, self.clock.now())
+    }
+
+    // POST the message to the subscriber in its encoding, signed again for each request. Fails
+    // if the node has a key but the request cannot be signed
+    pub(crate) fn post(
+        &self,
+        url: &str,
+        message: &Message,
+    ) -> Result<reqwest::RequestBuilder, AssistantError> {
+        let encoding = self
+            .encodings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(url)
+            .copied()
+            .unwrap_or_default();
+        let secret = self
+            .secrets
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(url)
+            .cloned();
+        let encoded = cloudevents::encode(encoding, message);
+        let now = self.clock.now();
+
+        let client = self
+            .client
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let mut request = client.post(url);
+        for (name, value) in encoded.headers {
+            request = request.header(name, value);
+        }
+        if let Some(identity) = &self.identity {
+            let signature = identity.sign(encoded.body.as_bytes(), now.timestamp())?;
+            for (name, value) in signature.headers() {
+                request = request.header(name, value);
+            }
+        }
+        if let Some(secret) = secret {
+            request = request.header(
+                webhook::SIGNATURE_HEADER,
+                webhook::sign(secret.as_bytes(), now.timestamp(), encoded.body.as_bytes()),
+            );
+        }
+        Ok(request.body(encoded.body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::TokioClock, identity};
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn signatures_and_events_share_the_time_of_the_clock() {
+        let url = "https://events.internal/gaianet";
+        let origin = chrono::Utc.with_ymd_and_hms(2024, 8, 1, 12, 0, 0).unwrap();
+        let secret = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let delivery = Delivery::new(
+            reqwest::Client::new(),
+            Some(NodeIdentity::from_secret(&hex::decode(secret).unwrap()).unwrap()),
+            HashMap::from([(url.to_string(), "whsec_0123456789".to_string())]),
+            HashMap::from([(url.to_string(), PayloadEncoding::CloudeventsBinary)]),
+            Arc::new(TokioClock::new(origin)),
+        );
+
+        let message = delivery.message(EventType::HealthChanged, None, r#"{"health":true}"#);
+        let request = delivery.post(url, &message).unwrap().build().unwrap();
+        let header = |name| request.headers()[name].to_str().unwrap();
+
+        assert_eq!(header(identity::TIMESTAMP_HEADER), "1722513600");
+        assert!(header(webhook::SIGNATURE_HEADER).starts_with("t=1722513600,"));
+        assert_eq!(header("ce-time"), "2024-08-01T12:00:00.000Z");
+    }
+}
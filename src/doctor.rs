@@ -0,0 +1,341 @@
+//! End-to-end diagnosis of a gaianet directory, behind `gaias doctor`.
+
+use crate::{
+    assistant::InfoExtras,
+    config::AssistantConfig,
+    consistency,
+    gaianet::{FrpcConfig, GaianetConfig, NodeConfig},
+    health::{HttpProber, Prober},
+    http,
+    hub::HubUrls,
+    identity::NodeIdentity,
+    info::retrieve_server_info,
+    integrity::ModelsLock,
+    models::{hash_model, node_models, Fingerprint, HashCache, ModelFile},
+    shutdown::Shutdown,
+};
+use serde::Serialize;
+use std::{fmt, fs::File, future::Future, path::Path, time::Duration};
+
+// time given to the API server to answer a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Outcome of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pass,
+    Fail,
+    /// Not run, because a check it depends on failed
+    Skip,
+}
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Pass => write!(f, "PASS"),
+            Status::Fail => write!(f, "FAIL"),
+            Status::Skip => write!(f, "SKIP"),
+        }
+    }
+}
+
+/// Result of a single check.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+/// Results of all the checks, in the order they ran.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+impl Report {
+    fn push(&mut self, name: impl Into<String>, status: Status, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        });
+        self.passed = self.checks.iter().all(|check| check.status == Status::Pass);
+    }
+
+    fn pass(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, Status::Pass, detail)
+    }
+
+    fn fail(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, Status::Fail, detail)
+    }
+
+    fn skip(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, Status::Skip, detail)
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .checks
+            .iter()
+            .map(|check| check.name.len())
+            .max()
+            .unwrap_or_default();
+        for check in self.checks.iter() {
+            writeln!(
+                f,
+                "[{}] {:width$}  {}",
+                check.status,
+                check.name,
+                check.detail,
+                width = width
+            )?;
+        }
+
+        let failed = self
+            .checks
+            .iter()
+            .filter(|check| check.status == Status::Fail)
+            .count();
+        let skipped = self
+            .checks
+            .iter()
+            .filter(|check| check.status == Status::Skip)
+            .count();
+        writeln!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.checks.len() - failed - skipped,
+            failed,
+            skipped
+        )
+    }
+}
+
+/// Check the gaianet directory, the models, the API server and the hub URLs.
+pub async fn diagnose(gaianet_dir: &Path, config: &AssistantConfig) -> Report {
+    let mut report = Report::default();
+
+    // node settings
+    let gaianet = match GaianetConfig::load(&gaianet_dir.join("config.json")).await {
+        Ok(gaianet) => {
+            report.pass("config.json", "parsed");
+            report.pass("domain", &gaianet.domain);
+            Some(gaianet)
+        }
+        Err(e) => {
+            report.fail("config.json", e.to_string());
+            report.skip("domain", "config.json is invalid");
+            None
+        }
+    };
+    let frpc = match FrpcConfig::load(&gaianet_dir.join(&config.paths.frpc_toml)).await {
+        Ok(frpc) => {
+            report.pass("frpc.toml", "parsed");
+            report.pass("device_id", frpc.device_id());
+            Some(frpc)
+        }
+        Err(e) => {
+            report.fail("frpc.toml", e.to_string());
+            report.skip("device_id", "frpc.toml is invalid");
+            None
+        }
+    };
+
+    // key signing the payloads
+    let nodeid = gaianet_dir.join(&config.paths.nodeid);
+    match nodeid.exists() {
+        true => match tokio::task::spawn_blocking(move || NodeIdentity::load(&nodeid)).await {
+            Ok(Ok(identity)) => report.pass("node_key", identity.address()),
+            Ok(Err(e)) => report.fail("node_key", e.to_string()),
+            Err(e) => report.fail("node_key", e.to_string()),
+        },
+        false => report.pass("node_key", "no nodeid.json, payloads are sent unsigned"),
+    }
+
+    // models
+    match (
+        &gaianet,
+        ModelsLock::load(&gaianet_dir.join(&config.paths.models_lock)),
+    ) {
+        (_, Err(e)) => report.fail("models.lock", e.to_string()),
+        (None, Ok(_)) => report.skip("models", "config.json is invalid"),
+        (Some(gaianet), Ok(lock)) => {
+            let models = node_models(gaianet_dir, gaianet, &lock);
+            if models.is_empty() {
+                report.fail("models", "no model configured in config.json");
+            }
+            let cache = HashCache::load(&gaianet_dir.join(&config.paths.hash_cache));
+            for model in models.iter() {
+                let name = format!("{}_model", model.kind);
+                match check_model(model, &cache).await {
+                    Ok(detail) => report.pass(name, detail),
+                    Err(detail) => report.fail(name, detail),
+                }
+            }
+        }
+    }
+
+    // log of the API server
+    let server_log = gaianet_dir.join(&config.paths.server_log);
+    match File::open(&server_log) {
+        Ok(_) => report.pass("server_log", server_log.display().to_string()),
+        Err(e) => report.fail("server_log", format!("{}: {}", server_log.display(), e)),
+    }
+
+    // HTTP client, the default one is used by the checks below if invalid
+    let client = match http::api_client(&config.http, &config.server_socket_addr) {
+        Ok(client) => {
+            report.pass("http_client", "built from [http]");
+            client
+        }
+        Err(e) => {
+            report.fail("http_client", e.to_string());
+            reqwest::Client::new()
+        }
+    };
+
+    // API key of the API server, the requests are unauthenticated if invalid
+    let api_key = match http::api_key(config, gaianet_dir) {
+        Ok(Some(key)) => {
+            report.pass("api_key", format!("{} characters", key.len()));
+            Some(key)
+        }
+        Ok(None) => {
+            report.pass("api_key", "none, the requests are unauthenticated");
+            None
+        }
+        Err(e) => {
+            report.fail("api_key", e.to_string());
+            None
+        }
+    };
+
+    // API server
+    let addr = &config.server_socket_addr;
+    let extras = InfoExtras::default();
+    match with_timeout(retrieve_server_info(
+        addr,
+        &extras,
+        &client,
+        api_key.as_deref(),
+    ))
+    .await
+    {
+        Ok(server_info) => {
+            report.pass("server_info", format!("/v1/info of {}", addr));
+            if let Some(gaianet) = &gaianet {
+                let warnings = consistency::check(&server_info, gaianet);
+                match warnings.is_empty() {
+                    true => report.pass("consistency", "matches config.json"),
+                    false => report.fail(
+                        "consistency",
+                        warnings
+                            .iter()
+                            .map(|warning| warning.message.as_str())
+                            .collect::<Vec<_>>()
+                            .join("; "),
+                    ),
+                }
+            }
+        }
+        Err(e) => report.fail("server_info", e),
+    }
+    let prober = HttpProber::new(addr.clone(), config.probe.clone())
+        .with_client(client)
+        .with_api_key(api_key);
+    match with_timeout(prober.ping()).await {
+        Ok(response) if response.status.is_success() => {
+            report.pass("chat_probe", format!("status {}", response.status))
+        }
+        Ok(response) => report.fail(
+            "chat_probe",
+            format!("status {}: {}", response.status, response.body.trim()),
+        ),
+        Err(e) => report.fail("chat_probe", e),
+    }
+
+    // hub
+    match (gaianet, frpc) {
+        (Some(config_json), Some(frpc)) => {
+            let node = NodeConfig {
+                config: config_json,
+                frpc,
+                server_log_file: server_log,
+            };
+            match HubUrls::new(&config.hub, &node) {
+                Ok(urls) => report.pass(
+                    "hub_urls",
+                    format!("{}, {}", urls.info.join(", "), urls.health.join(", ")),
+                ),
+                Err(e) => report.fail("hub_urls", e),
+            }
+        }
+        _ => report.skip("hub_urls", "config.json or frpc.toml is invalid"),
+    }
+
+    report
+}
+
+// The model exists and matches its expected sha256, if any
+async fn check_model(model: &ModelFile, cache: &HashCache) -> Result<String, String> {
+    let fingerprint =
+        Fingerprint::of(&model.path).map_err(|e| format!("{}: {}", model.path.display(), e))?;
+    let Some(expected) = &model.expected_sha256 else {
+        return Ok(format!("{} (no expected sha256)", model.path.display()));
+    };
+
+    let sha256 = match cache.get(&model.path, &fingerprint) {
+        Some(sha256) => sha256.to_string(),
+        None => {
+            let model = model.clone();
+            tokio::task::spawn_blocking(move || hash_model(&model, &Shutdown::new()))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default()
+        }
+    };
+    match expected.eq_ignore_ascii_case(&sha256) {
+        true => Ok(format!("{} matches {}", model.path.display(), expected)),
+        false => Err(format!(
+            "{}: expected sha256 {}, found {}",
+            model.path.display(),
+            expected,
+            sha256
+        )),
+    }
+}
+
+async fn with_timeout<T, E: fmt::Display>(
+    request: impl Future<Output = Result<T, E>>,
+) -> Result<T, String> {
+    match tokio::time::timeout(REQUEST_TIMEOUT, request).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!(
+            "no response within {} seconds",
+            REQUEST_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_fails_unless_all_checks_pass() {
+        let mut report = Report::default();
+        report.pass("config.json", "parsed");
+        assert!(report.passed);
+
+        report.skip("domain", "config.json is invalid");
+        assert!(!report.passed);
+        assert_eq!(
+            report.to_string(),
+            "[PASS] config.json  parsed\n[SKIP] domain       config.json is invalid\n1 passed, 0 failed, 1 skipped\n"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Dry-run mode: the payloads are written out instead of being sent to the subscribers.
+
+use crate::error::AssistantError;
+use jsonschema::JSONSchema;
+use log::{error, info};
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// JSON schema of the `device-info` payloads.
+pub const DEVICE_INFO_SCHEMA: &str = include_str!("../schemas/device-info.schema.json");
+/// JSON schema of the `device-health` payloads.
+pub const DEVICE_HEALTH_SCHEMA: &str = include_str!("../schemas/device-health.schema.json");
+
+/// Where the payloads are written in dry-run mode, one JSON record per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DryRunOutput {
+    Stdout,
+    File(PathBuf),
+}
+
+/// Payload sent to the subscribers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Payload {
+    DeviceInfo,
+    DeviceHealth,
+}
+impl Payload {
+    fn name(&self) -> &'static str {
+        match self {
+            Payload::DeviceInfo => "device-info",
+            Payload::DeviceHealth => "device-health",
+        }
+    }
+}
+
+// Writes the payloads, validated against their schema
+pub(crate) struct DryRun {
+    output: Mutex<Box<dyn Write + Send>>,
+    device_info: JSONSchema,
+    device_health: JSONSchema,
+}
+impl DryRun {
+    pub(crate) fn new(output: &DryRunOutput) -> Result<Self, AssistantError> {
+        let writer: Box<dyn Write + Send> = match output {
+            DryRunOutput::Stdout => Box::new(io::stdout()),
+            DryRunOutput::File(path) => Box::new(File::create(path).map_err(|e| {
+                let err_msg = format!("Failed to create {}: {}", path.display(), e);
+                error!("{}", &err_msg);
+                AssistantError::ArgumentError(err_msg)
+            })?),
+        };
+        info!("Dry run: payloads are written to {:?}", output);
+
+        Ok(Self {
+            output: Mutex::new(writer),
+            device_info: compile(DEVICE_INFO_SCHEMA),
+            device_health: compile(DEVICE_HEALTH_SCHEMA),
+        })
+    }
+
+    // Write the payload that would be sent to the urls, with the schema violations if any
+    pub(crate) fn write<'a>(
+        &self,
+        kind: Payload,
+        urls: impl IntoIterator<Item = &'a String>,
+        payload: &Value,
+    ) {
+        let errors = self.validate(kind, payload);
+        for e in errors.iter() {
+            error!("Invalid {} payload: {}", kind.name(), e);
+        }
+
+        let mut urls: Vec<&String> = urls.into_iter().collect();
+        urls.sort();
+        let mut record = json!({
+            "type": kind.name(),
+            "urls": urls,
+            "payload": payload,
+            "valid": errors.is_empty(),
+        });
+        if !errors.is_empty() {
+            record["errors"] = json!(errors);
+        }
+
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(output, "{}", record).and_then(|_| output.flush()) {
+            error!("Failed to write the {} payload: {}", kind.name(), e);
+        }
+    }
+
+    // Schema violations of the payload, with the path of the offending value
+    pub(crate) fn validate(&self, kind: Payload, payload: &Value) -> Vec<String> {
+        let schema = match kind {
+            Payload::DeviceInfo => &self.device_info,
+            Payload::DeviceHealth => &self.device_health,
+        };
+        match schema.validate(payload) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|e| match e.instance_path.to_string().as_str() {
+                    "" => e.to_string(),
+                    path => format!("{}: {}", path, e),
+                })
+                .collect(),
+        }
+    }
+}
+
+// the schemas are part of the crate, and checked by the tests
+fn compile(schema: &str) -> JSONSchema {
+    let schema: Value = serde_json::from_str(schema).expect("invalid JSON schema");
+    JSONSchema::compile(&schema).expect("invalid JSON schema")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dry_run() -> DryRun {
+        DryRun {
+            output: Mutex::new(Box::new(io::sink())),
+            device_info: compile(DEVICE_INFO_SCHEMA),
+            device_health: compile(DEVICE_HEALTH_SCHEMA),
+        }
+    }
+
+    #[test]
+    fn health_payloads_are_validated() {
+        let dry_run = dry_run();
+
+        for payload in [
+            json!({ "health": true }),
+            json!({ "health": false, "reason": "assistant_shutdown" }),
+        ] {
+            assert_eq!(
+                dry_run.validate(Payload::DeviceHealth, &payload),
+                Vec::<String>::new()
+            );
+        }
+
+        let mut v2 = json!({
+            "schema_version": 2,
+            "device_id": "device-1",
+            "assistant_version": "0.5.0",
+            "timestamp": "2024-08-01T00:00:00Z",
+            "sequence": 1,
+            "status": "degraded",
+            "reasons": ["prompt_template_mismatch"],
+            "since_change": 0,
+            "last_successful_probe": null,
+            "requests": { "total": 12, "failed": 1 },
+            "uptime": { "assistant": 60, "api_server": null }
+        });
+        assert_eq!(
+            dry_run.validate(Payload::DeviceHealth, &v2),
+            Vec::<String>::new()
+        );
+
+        // the legacy payloads carry nothing else, as older hubs may reject it
+        for payload in [
+            json!({ "health": "yes" }),
+            json!({ "health": true, "degraded": ["prompt_template_mismatch"] }),
+            json!({ "health": true, "sequence": 1, "since_change": 0 }),
+        ] {
+            assert!(!dry_run.validate(Payload::DeviceHealth, &payload).is_empty());
+        }
+        v2["status"] = json!("ok");
+        assert!(!dry_run.validate(Payload::DeviceHealth, &v2).is_empty());
+    }
+
+    #[test]
+    fn info_payloads_are_validated() {
+        let dry_run = dry_run();
+        let mut info = json!({
+            "api_server": { "type": "chat", "version": "0.9.3" },
+            "chat_model": { "name": "Llama-3-8B-Instruct", "ctx_size": 16384 },
+            "extras": { "system_prompt": "" }
+        });
+        assert_eq!(
+            dry_run.validate(Payload::DeviceInfo, &info),
+            Vec::<String>::new()
+        );
+
+        info["chat_model"]["sha256"] = json!("ABC");
+        let errors = dry_run.validate(Payload::DeviceInfo, &info);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("/chat_model/sha256: "),
+            "{:?}",
+            errors
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Address of the LlamaEdge API server.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Where the LlamaEdge API server listens, parsed from `host:port`, `http://host:port`,
+/// `https://host:port` or `unix:/path/to/socket`.
+///
+/// The host is an IPv4 or IPv6 address, or a hostname. The wildcard addresses the server
+/// binds to, `0.0.0.0` and `[::]`, are reached on the loopback address of the same family.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ApiServerEndpoint {
+    /// TCP address, over https if `https` is set
+    Tcp {
+        https: bool,
+        /// IP address, IPv6 in brackets, or hostname
+        host: String,
+        port: u16,
+    },
+    /// Unix domain socket
+    Unix(PathBuf),
+}
+impl ApiServerEndpoint {
+    /// URL of the `path` of the API server. Requests over a Unix domain socket must be sent
+    /// with a client connecting to [`unix_socket`](Self::unix_socket), see
+    /// [`crate::http::api_client`].
+    pub fn url(&self, path: &str) -> String {
+        match self {
+            ApiServerEndpoint::Tcp { https, host, port } => {
+                let scheme = match https {
+                    true => "https",
+                    false => "http",
+                };
+                let host = match host.as_str() {
+                    "0.0.0.0" => "127.0.0.1",
+                    "[::]" => "[::1]",
+                    host => host,
+                };
+                format!("{}://{}:{}{}", scheme, host, port, path)
+            }
+            // the host is only sent in the Host header
+            ApiServerEndpoint::Unix(_) => format!("http://localhost{}", path),
+        }
+    }
+
+    /// Path of the Unix domain socket, if the API server listens on one
+    pub fn unix_socket(&self) -> Option<&Path> {
+        match self {
+            ApiServerEndpoint::Unix(path) => Some(path),
+            ApiServerEndpoint::Tcp { .. } => None,
+        }
+    }
+}
+impl FromStr for ApiServerEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            // `unix:/run/llamaedge.sock` or `unix:///run/llamaedge.sock`
+            let path = path.strip_prefix("//").unwrap_or(path);
+            if path.is_empty() {
+                return Err(format!("{}: missing socket path", s));
+            }
+            return Ok(ApiServerEndpoint::Unix(PathBuf::from(path)));
+        }
+
+        // the port is required without scheme, as in the address LlamaEdge listens on
+        let url = match s.contains("://") {
+            true => s.to_string(),
+            false => format!("http://{}", s),
+        };
+        let parsed = reqwest::Url::parse(&url).map_err(|e| format!("{}: {}", s, e))?;
+        let https = match parsed.scheme() {
+            "http" => false,
+            "https" => true,
+            scheme => return Err(format!("{}: unsupported scheme {}", s, scheme)),
+        };
+        if !matches!(parsed.path(), "" | "/") || parsed.query().is_some() {
+            return Err(format!("{}: unexpected path", s));
+        }
+        let host = match parsed.host_str() {
+            Some(host) if !host.is_empty() => host.to_string(),
+            _ => return Err(format!("{}: missing host", s)),
+        };
+        // the URL drops the default port of the scheme, e.g. in `0.0.0.0:80`
+        let port = match (parsed.port(), s.contains("://")) {
+            (Some(port), _) => port,
+            (None, true) => parsed.port_or_known_default().unwrap_or(80),
+            (None, false) => s
+                .rsplit_once(':')
+                .and_then(|(_, port)| port.parse().ok())
+                .ok_or_else(|| format!("{}: missing port", s))?,
+        };
+
+        Ok(ApiServerEndpoint::Tcp { https, host, port })
+    }
+}
+impl fmt::Display for ApiServerEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiServerEndpoint::Tcp {
+                https: true,
+                host,
+                port,
+            } => write!(f, "https://{}:{}", host, port),
+            ApiServerEndpoint::Tcp {
+                https: false,
+                host,
+                port,
+            } => write!(f, "{}:{}", host, port),
+            ApiServerEndpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+impl From<SocketAddr> for ApiServerEndpoint {
+    fn from(addr: SocketAddr) -> Self {
+        let host = match addr {
+            SocketAddr::V4(addr) => addr.ip().to_string(),
+            SocketAddr::V6(addr) => format!("[{}]", addr.ip()),
+        };
+        ApiServerEndpoint::Tcp {
+            https: false,
+            host,
+            port: addr.port(),
+        }
+    }
+}
+impl TryFrom<String> for ApiServerEndpoint {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+impl From<ApiServerEndpoint> for String {
+    fn from(endpoint: ApiServerEndpoint) -> Self {
+        endpoint.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(endpoint: &str) -> String {
+        endpoint
+            .parse::<ApiServerEndpoint>()
+            .unwrap()
+            .url("/v1/info")
+    }
+
+    #[test]
+    fn wildcard_addresses_are_reached_on_loopback() {
+        assert_eq!(url("0.0.0.0:8080"), "http://127.0.0.1:8080/v1/info");
+        assert_eq!(url("[::]:8080"), "http://[::1]:8080/v1/info");
+        assert_eq!(url("[::1]:8080"), "http://[::1]:8080/v1/info");
+        assert_eq!(url("10.0.0.5:8080"), "http://10.0.0.5:8080/v1/info");
+    }
+
+    #[test]
+    fn default_port_is_kept() {
+        assert_eq!(url("0.0.0.0:80"), "http://127.0.0.1:80/v1/info");
+        assert_eq!(url("localhost:80"), "http://localhost:80/v1/info");
+        assert_eq!(url("[::]:80"), "http://[::1]:80/v1/info");
+        assert_eq!(url("http://localhost"), "http://localhost:80/v1/info");
+        assert_eq!(
+            url("https://node.internal:443"),
+            "https://node.internal:443/v1/info"
+        );
+        assert_eq!(
+            "[::]:80".parse::<ApiServerEndpoint>().unwrap().to_string(),
+            "[::]:80"
+        );
+    }
+
+    #[test]
+    fn hostnames_and_https_are_supported() {
+        assert_eq!(url("llamaedge:8080"), "http://llamaedge:8080/v1/info");
+        assert_eq!(
+            url("https://node.internal:8443/"),
+            "https://node.internal:8443/v1/info"
+        );
+        assert_eq!(
+            url("https://node.internal"),
+            "https://node.internal:443/v1/info"
+        );
+    }
+
+    #[test]
+    fn unix_sockets_are_supported() {
+        for s in ["unix:/run/llamaedge.sock", "unix:///run/llamaedge.sock"] {
+            let endpoint: ApiServerEndpoint = s.parse().unwrap();
+            assert_eq!(
+                endpoint.unix_socket(),
+                Some(Path::new("/run/llamaedge.sock"))
+            );
+            assert_eq!(endpoint.url("/v1/info"), "http://localhost/v1/info");
+            assert_eq!(endpoint.to_string(), "unix:/run/llamaedge.sock");
+        }
+    }
+
+    #[test]
+    fn endpoints_round_trip() {
+        for s in [
+            "0.0.0.0:8080",
+            "[::]:8080",
+            "llamaedge:8080",
+            "https://node.internal:8443",
+            "unix:/run/llamaedge.sock",
+        ] {
+            assert_eq!(s.parse::<ApiServerEndpoint>().unwrap().to_string(), s);
+        }
+        let addr: SocketAddr = "[::]:8080".parse().unwrap();
+        assert_eq!(ApiServerEndpoint::from(addr).to_string(), "[::]:8080");
+    }
+
+    #[test]
+    fn invalid_endpoints_are_rejected() {
+        for s in [
+            "localhost",
+            "[::]",
+            "localhost:http",
+            "unix:",
+            "ftp://node.internal:21",
+            "http://node.internal:8080/v1",
+            ":8080",
+        ] {
+            assert!(s.parse::<ApiServerEndpoint>().is_err(), "{}", s);
+        }
+    }
+}
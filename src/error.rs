@@ -8,9 +8,15 @@ pub enum AssistantError {
     /// Error returned while parsing CLI options failed
     #[error("{0}")]
     ArgumentError(String),
+    /// Error returned while loading the configuration failed
+    #[error("{0}")]
+    ConfigError(String),
     /// Error returned while sending a request
     #[error("Failed to send request for checking API server health: {0}")]
     ServerDownError(String),
+    /// Error returned when the API server rejects the API key
+    #[error("{0}")]
+    Unauthorized(String),
     /// Generic error returned while performing an operation
     #[error("{0}")]
     Operation(String),
@@ -0,0 +1,288 @@
+use crate::{config::PathsConfig, error::AssistantError, health::is_file};
+use log::{error, info};
+use serde::{de::DeserializeOwned, de::Error as _, Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+
+/// Settings of a gaianet node, read from the gaianet directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeConfig {
+    /// Settings from `config.json`
+    pub config: GaianetConfig,
+    /// Settings from `gaia-frp/frpc.toml`
+    pub frpc: FrpcConfig,
+    /// Log file of the API server
+    pub server_log_file: PathBuf,
+}
+impl NodeConfig {
+    /// Read the node settings from `config.json`, `frpc.toml` and the API server log in the
+    /// given gaianet directory.
+    pub async fn load(
+        gaianet_dir: impl AsRef<Path>,
+        paths: &PathsConfig,
+    ) -> Result<Self, AssistantError> {
+        let gaianet_dir = gaianet_dir.as_ref();
+
+        let server_log_file = gaianet_dir.join(&paths.server_log);
+        if !server_log_file.exists() || !is_file(&server_log_file).await {
+            let err_msg = format!("Invalid log file path: {}", &server_log_file.display());
+            error!("{}", &err_msg);
+            return Err(AssistantError::ArgumentError(err_msg));
+        }
+        info!("Log file of API server: {}", &server_log_file.display());
+
+        let frpc = FrpcConfig::load(&gaianet_dir.join(&paths.frpc_toml)).await?;
+        info!("Device ID: {}", frpc.device_id());
+
+        let config = GaianetConfig::load(&gaianet_dir.join("config.json")).await?;
+        info!("Domain: {}", &config.domain);
+        info!("System prompt: {}", &config.system_prompt);
+        info!("RAG prompt: {}", &config.rag_prompt);
+
+        Ok(Self {
+            config,
+            frpc,
+            server_log_file,
+        })
+    }
+
+    /// Device ID of the node
+    pub fn device_id(&self) -> &str {
+        self.frpc.device_id()
+    }
+}
+
+/// Settings of the gaianet `config.json` used by the assistant. Other fields are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct GaianetConfig {
+    /// Domain of the node
+    pub domain: String,
+    /// URL of the chat model. Empty if not configured
+    #[serde(default, rename = "chat")]
+    pub chat_url: String,
+    /// URL of the embedding model. Empty if not configured
+    #[serde(default, rename = "embedding")]
+    pub embedding_url: String,
+    /// Expected sha256 of the chat model. Empty if not configured
+    #[serde(default)]
+    pub chat_sha256: String,
+    /// Expected sha256 of the embedding model. Empty if not configured
+    #[serde(default)]
+    pub embedding_sha256: String,
+    /// Context size of the chat model, if configured
+    #[serde(default, deserialize_with = "ctx_size")]
+    pub chat_ctx_size: Option<u64>,
+    /// Context size of the embedding model, if configured
+    #[serde(default, deserialize_with = "ctx_size")]
+    pub embedding_ctx_size: Option<u64>,
+    /// Prompt template of the chat model. Empty if not configured
+    #[serde(default)]
+    pub prompt_template: String,
+    /// System prompt. Empty if not configured
+    #[serde(default)]
+    pub system_prompt: String,
+    /// RAG prompt. Empty if not configured
+    #[serde(default)]
+    pub rag_prompt: String,
+}
+impl GaianetConfig {
+    /// Read and validate `config.json`
+    pub async fn load(path: &Path) -> Result<Self, AssistantError> {
+        let content = read(path).await?;
+        Self::from_json(&content).map_err(|e| invalid(path, e))
+    }
+
+    /// Parse and validate the content of `config.json`. Errors give the path of the field
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        let config: Self = deserialize(value)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.domain.trim().is_empty() {
+            return Err("domain: must not be empty".to_string());
+        }
+        for (field, sha256) in [
+            ("chat_sha256", &self.chat_sha256),
+            ("embedding_sha256", &self.embedding_sha256),
+        ] {
+            if !sha256.is_empty()
+                && (sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()))
+            {
+                return Err(format!(
+                    "{}: expected 64 hexadecimal digits, found `{}`",
+                    field, sha256
+                ));
+            }
+        }
+        for (field, size) in [
+            ("chat_ctx_size", self.chat_ctx_size),
+            ("embedding_ctx_size", self.embedding_ctx_size),
+        ] {
+            if size == Some(0) {
+                return Err(format!("{}: must be greater than 0", field));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings of `frpc.toml` used by the assistant. Other fields are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FrpcConfig {
+    pub metadatas: FrpcMetadatas,
+}
+
+/// `[metadatas]` of `frpc.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FrpcMetadatas {
+    /// Device ID of the node
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+}
+
+impl FrpcConfig {
+    /// Read and validate `frpc.toml`
+    pub async fn load(path: &Path) -> Result<Self, AssistantError> {
+        let content = read(path).await?;
+        Self::from_toml(&content).map_err(|e| invalid(path, e))
+    }
+
+    /// Parse and validate the content of `frpc.toml`. Errors give the path of the field
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        let value: toml::Value = toml::from_str(content).map_err(|e| e.message().to_string())?;
+        let config: Self = deserialize(value)?;
+        if config.metadatas.device_id.trim().is_empty() {
+            return Err("metadatas.deviceId: must not be empty".to_string());
+        }
+        Ok(config)
+    }
+
+    /// Device ID of the node
+    pub fn device_id(&self) -> &str {
+        &self.metadatas.device_id
+    }
+}
+
+async fn read(path: &Path) -> Result<String, AssistantError> {
+    if !is_file(path).await {
+        let err_msg = format!("Invalid {} file path: {}", file_name(path), path.display());
+        error!("{}", &err_msg);
+        return Err(AssistantError::ArgumentError(err_msg));
+    }
+
+    tokio::fs::read_to_string(path).await.map_err(|e| {
+        let err_msg = format!("Failed to read {}: {}", path.display(), e);
+        error!("{}", &err_msg);
+        AssistantError::ConfigError(err_msg)
+    })
+}
+
+fn invalid(path: &Path, e: String) -> AssistantError {
+    let err_msg = format!("Invalid {}: {}", path.display(), e);
+    error!("{}", &err_msg);
+    AssistantError::ConfigError(err_msg)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+// Deserialize, giving the path of the offending field on errors
+fn deserialize<'de, T, D>(deserializer: D) -> Result<T, String>
+where
+    T: DeserializeOwned,
+    D: Deserializer<'de>,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        // toml repeats the key on another line
+        let inner = e.inner().to_string();
+        let inner = inner.lines().next().unwrap_or_default();
+        match e.path().to_string().as_str() {
+            "." => inner.to_string(),
+            path => format!("{}: {}", path, inner),
+        }
+    })
+}
+
+// gaianet writes the context sizes as strings, e.g. `"chat_ctx_size": "16384"`
+fn ctx_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CtxSize {
+        Number(u64),
+        Text(String),
+    }
+
+    match Option::<CtxSize>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(CtxSize::Number(size)) => Ok(Some(size)),
+        Some(CtxSize::Text(size)) if size.trim().is_empty() => Ok(None),
+        Some(CtxSize::Text(size)) => size
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| D::Error::custom(format!("invalid context size `{}`", size))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_json_defaults_missing_fields() {
+        let config = GaianetConfig::from_json(
+            r#"{ "domain": "gaia.domains", "chat_ctx_size": "16384", "embedding_ctx_size": 8192, "llamaedge_port": "8080" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            GaianetConfig {
+                domain: "gaia.domains".to_string(),
+                chat_ctx_size: Some(16384),
+                embedding_ctx_size: Some(8192),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn config_json_errors_give_the_field() {
+        let err = GaianetConfig::from_json(r#"{ "chat": "x" }"#).unwrap_err();
+        assert_eq!(err, "missing field `domain`");
+
+        let err =
+            GaianetConfig::from_json(r#"{ "domain": "d", "chat_ctx_size": "big" }"#).unwrap_err();
+        assert_eq!(err, "chat_ctx_size: invalid context size `big`");
+
+        let err = GaianetConfig::from_json(r#"{ "domain": "d", "system_prompt": 1 }"#).unwrap_err();
+        assert!(err.starts_with("system_prompt: invalid type"), "{}", err);
+
+        let err =
+            GaianetConfig::from_json(r#"{ "domain": "d", "chat_sha256": "abc" }"#).unwrap_err();
+        assert_eq!(
+            err,
+            "chat_sha256: expected 64 hexadecimal digits, found `abc`"
+        );
+    }
+
+    #[test]
+    fn frpc_toml_gives_the_device_id() {
+        let frpc = FrpcConfig::from_toml(
+            "serverAddr = \"gaia.domains\"\n[metadatas]\ndeviceId = \"device-1\"\n",
+        )
+        .unwrap();
+        assert_eq!(frpc.device_id(), "device-1");
+
+        let err = FrpcConfig::from_toml("[metadatas]\ndevice = \"device-1\"\n").unwrap_err();
+        assert_eq!(err, "metadatas: missing field `deviceId`");
+
+        let err = FrpcConfig::from_toml("[metadatas]\ndeviceId = \"\"\n").unwrap_err();
+        assert_eq!(err, "metadatas.deviceId: must not be empty");
+    }
+}
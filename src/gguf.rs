@@ -0,0 +1,429 @@
+use crate::error::AssistantError;
+use log::{info, warn};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+// "GGUF" in little endian
+const GGUF_MAGIC: u32 = 0x4655_4747;
+// bounds rejecting corrupted headers before allocating
+const MAX_STRING_LEN: u64 = 1024 * 1024;
+const MAX_TENSORS: u64 = 1_000_000;
+const MAX_DIMS: u32 = 8;
+
+// types of the metadata values
+const TYPE_U8: u32 = 0;
+const TYPE_I8: u32 = 1;
+const TYPE_U16: u32 = 2;
+const TYPE_I16: u32 = 3;
+const TYPE_U32: u32 = 4;
+const TYPE_I32: u32 = 5;
+const TYPE_F32: u32 = 6;
+const TYPE_BOOL: u32 = 7;
+const TYPE_STRING: u32 = 8;
+const TYPE_ARRAY: u32 = 9;
+const TYPE_U64: u32 = 10;
+const TYPE_I64: u32 = 11;
+const TYPE_F64: u32 = 12;
+
+/// Model details read from the header of a GGUF file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub(crate) struct GgufMetadata {
+    pub(crate) version: u32,
+    pub(crate) architecture: Option<String>,
+    /// Sum of the elements of all tensors
+    pub(crate) parameter_count: u64,
+    pub(crate) quantization: Option<String>,
+    pub(crate) context_length: Option<u64>,
+    pub(crate) embedding_length: Option<u64>,
+    pub(crate) tokenizer: Option<String>,
+}
+
+// Metadata value, kept only for scalars and strings
+#[derive(Debug, Clone, PartialEq)]
+enum MetaValue {
+    Uint(u64),
+    Int(i64),
+    Str(String),
+    Other,
+}
+impl MetaValue {
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            MetaValue::Uint(value) => Some(*value),
+            MetaValue::Int(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    fn into_string(self) -> Option<String> {
+        match self {
+            MetaValue::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Read the metadata of a GGUF model file. Blocking, as the tokenizer vocabulary in the
+/// header is skipped through.
+pub(crate) fn read_metadata(path: &Path) -> Result<GgufMetadata, AssistantError> {
+    let file = File::open(path).map_err(|e| {
+        AssistantError::Operation(format!("Failed to open {}: {}", path.display(), e))
+    })?;
+
+    let metadata = parse(&mut BufReader::new(file)).map_err(|e| {
+        AssistantError::Operation(format!(
+            "Failed to read the GGUF header of {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    info!("GGUF metadata of {}: {:?}", path.display(), metadata);
+
+    Ok(metadata)
+}
+
+// Read the metadata of the model at `path`, or None if it is not a valid GGUF file
+pub(crate) async fn load_metadata(path: &Path) -> Option<GgufMetadata> {
+    let path = path.to_path_buf();
+    match tokio::task::spawn_blocking(move || read_metadata(&path)).await {
+        Ok(Ok(metadata)) => Some(metadata),
+        Ok(Err(e)) => {
+            warn!("{}", e);
+            None
+        }
+        Err(e) => {
+            warn!("Failed to read the GGUF header: {}", e);
+            None
+        }
+    }
+}
+
+fn parse<R: Read>(reader: &mut R) -> io::Result<GgufMetadata> {
+    if read_u32(reader)? != GGUF_MAGIC {
+        return Err(invalid("not a GGUF file"));
+    }
+    let version = read_u32(reader)?;
+    if !(2..=3).contains(&version) {
+        return Err(invalid(format!("unsupported GGUF version {}", version)));
+    }
+    let tensor_count = read_u64(reader)?;
+    if tensor_count > MAX_TENSORS {
+        return Err(invalid(format!("too many tensors: {}", tensor_count)));
+    }
+    let kv_count = read_u64(reader)?;
+
+    let mut values = Vec::new();
+    for _ in 0..kv_count {
+        let key = read_string(reader)?;
+        let value_type = read_u32(reader)?;
+        let value = read_value(reader, value_type)?;
+        if value != MetaValue::Other {
+            values.push((key, value));
+        }
+    }
+    let mut take = |key: &str| {
+        values
+            .iter()
+            .position(|(name, _)| name == key)
+            .map(|index| values.swap_remove(index).1)
+    };
+
+    let architecture = take("general.architecture").and_then(MetaValue::into_string);
+    let quantization = take("general.file_type")
+        .and_then(|value| value.as_u64())
+        .map(file_type_name);
+    let tokenizer = take("tokenizer.ggml.model").and_then(MetaValue::into_string);
+    let (context_length, embedding_length) = match &architecture {
+        Some(arch) => (
+            take(&format!("{}.context_length", arch)).and_then(|value| value.as_u64()),
+            take(&format!("{}.embedding_length", arch)).and_then(|value| value.as_u64()),
+        ),
+        None => (None, None),
+    };
+
+    // the parameters are counted from the tensors, rather than trusting the metadata
+    let mut parameter_count = 0u64;
+    for _ in 0..tensor_count {
+        skip_string(reader)?;
+        let n_dims = read_u32(reader)?;
+        if n_dims > MAX_DIMS {
+            return Err(invalid(format!("too many dimensions: {}", n_dims)));
+        }
+        let mut elements = 1u64;
+        for _ in 0..n_dims {
+            elements = elements.saturating_mul(read_u64(reader)?);
+        }
+        // tensor type and offset
+        skip(reader, 4 + 8)?;
+        parameter_count = parameter_count.saturating_add(elements);
+    }
+
+    Ok(GgufMetadata {
+        version,
+        architecture,
+        parameter_count,
+        quantization,
+        context_length,
+        embedding_length,
+        tokenizer,
+    })
+}
+
+fn read_value<R: Read>(reader: &mut R, value_type: u32) -> io::Result<MetaValue> {
+    let value = match value_type {
+        TYPE_U8 => MetaValue::Uint(read_array::<1, R>(reader)?[0] as u64),
+        TYPE_I8 => MetaValue::Int(read_array::<1, R>(reader)?[0] as i8 as i64),
+        TYPE_U16 => MetaValue::Uint(u16::from_le_bytes(read_array(reader)?) as u64),
+        TYPE_I16 => MetaValue::Int(i16::from_le_bytes(read_array(reader)?) as i64),
+        TYPE_U32 => MetaValue::Uint(read_u32(reader)? as u64),
+        TYPE_I32 => MetaValue::Int(i32::from_le_bytes(read_array(reader)?) as i64),
+        TYPE_U64 => MetaValue::Uint(read_u64(reader)?),
+        TYPE_I64 => MetaValue::Int(i64::from_le_bytes(read_array(reader)?)),
+        TYPE_STRING => MetaValue::Str(read_string(reader)?),
+        TYPE_ARRAY => {
+            let item_type = read_u32(reader)?;
+            let len = read_u64(reader)?;
+            match item_type {
+                TYPE_STRING => {
+                    for _ in 0..len {
+                        skip_string(reader)?;
+                    }
+                }
+                TYPE_ARRAY => return Err(invalid("nested arrays are not supported")),
+                _ => skip(reader, len.saturating_mul(scalar_size(item_type)?))?,
+            }
+            MetaValue::Other
+        }
+        _ => {
+            skip(reader, scalar_size(value_type)?)?;
+            MetaValue::Other
+        }
+    };
+
+    Ok(value)
+}
+
+fn scalar_size(value_type: u32) -> io::Result<u64> {
+    match value_type {
+        TYPE_U8 | TYPE_I8 | TYPE_BOOL => Ok(1),
+        TYPE_U16 | TYPE_I16 => Ok(2),
+        TYPE_U32 | TYPE_I32 | TYPE_F32 => Ok(4),
+        TYPE_U64 | TYPE_I64 | TYPE_F64 => Ok(8),
+        _ => Err(invalid(format!("unknown value type {}", value_type))),
+    }
+}
+
+// Name of the `general.file_type`, as in llama.cpp
+fn file_type_name(file_type: u64) -> String {
+    let name = match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        other => return format!("unknown ({})", other),
+    };
+
+    name.to_string()
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn read_array<const N: usize, R: Read>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(reader)?))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_array(reader)?))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_u64(reader)?;
+    if len > MAX_STRING_LEN {
+        return Err(invalid(format!("string too long: {} bytes", len)));
+    }
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))
+}
+
+fn skip_string<R: Read>(reader: &mut R) -> io::Result<()> {
+    let len = read_u64(reader)?;
+    if len > MAX_STRING_LEN {
+        return Err(invalid(format!("string too long: {} bytes", len)));
+    }
+    skip(reader, len)
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    match skipped == len {
+        true => Ok(()),
+        false => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Builder {
+        kvs: Vec<u8>,
+        kv_count: u64,
+        tensors: Vec<u8>,
+        tensor_count: u64,
+    }
+    impl Builder {
+        fn key(&mut self, key: &str, value_type: u32) -> &mut Vec<u8> {
+            put_string(&mut self.kvs, key);
+            self.kvs.extend(value_type.to_le_bytes());
+            self.kv_count += 1;
+            &mut self.kvs
+        }
+
+        fn string(mut self, key: &str, value: &str) -> Self {
+            put_string(self.key(key, TYPE_STRING), value);
+            self
+        }
+
+        fn u32(mut self, key: &str, value: u32) -> Self {
+            self.key(key, TYPE_U32).extend(value.to_le_bytes());
+            self
+        }
+
+        fn tokens(mut self, tokens: &[&str]) -> Self {
+            let kvs = self.key("tokenizer.ggml.tokens", TYPE_ARRAY);
+            kvs.extend(TYPE_STRING.to_le_bytes());
+            kvs.extend((tokens.len() as u64).to_le_bytes());
+            for token in tokens {
+                put_string(kvs, token);
+            }
+            self
+        }
+
+        fn tensor(mut self, name: &str, dims: &[u64]) -> Self {
+            put_string(&mut self.tensors, name);
+            self.tensors.extend((dims.len() as u32).to_le_bytes());
+            for dim in dims {
+                self.tensors.extend(dim.to_le_bytes());
+            }
+            self.tensors.extend(12u32.to_le_bytes());
+            self.tensors.extend(0u64.to_le_bytes());
+            self.tensor_count += 1;
+            self
+        }
+
+        fn build(self) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            bytes.extend(GGUF_MAGIC.to_le_bytes());
+            bytes.extend(3u32.to_le_bytes());
+            bytes.extend(self.tensor_count.to_le_bytes());
+            bytes.extend(self.kv_count.to_le_bytes());
+            bytes.extend(self.kvs);
+            bytes.extend(self.tensors);
+            bytes
+        }
+    }
+
+    fn put_string(bytes: &mut Vec<u8>, value: &str) {
+        bytes.extend((value.len() as u64).to_le_bytes());
+        bytes.extend(value.as_bytes());
+    }
+
+    #[test]
+    fn header_is_summarized() {
+        let bytes = Builder::default()
+            .string("general.architecture", "llama")
+            .u32("general.file_type", 17)
+            .u32("llama.context_length", 8192)
+            .u32("llama.embedding_length", 4096)
+            .string("tokenizer.ggml.model", "gpt2")
+            .tokens(&["<s>", "</s>", "hello"])
+            .tensor("token_embd.weight", &[4096, 128256])
+            .tensor("output_norm.weight", &[4096])
+            .build();
+
+        let metadata = parse(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            metadata,
+            GgufMetadata {
+                version: 3,
+                architecture: Some("llama".to_string()),
+                parameter_count: 4096 * 128256 + 4096,
+                quantization: Some("Q5_K_M".to_string()),
+                context_length: Some(8192),
+                embedding_length: Some(4096),
+                tokenizer: Some("gpt2".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn missing_keys_are_none() {
+        let bytes = Builder::default()
+            .string("general.architecture", "nomic-bert")
+            .build();
+
+        let metadata = parse(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(metadata.architecture.as_deref(), Some("nomic-bert"));
+        assert_eq!(metadata.context_length, None);
+        assert_eq!(metadata.quantization, None);
+        assert_eq!(metadata.parameter_count, 0);
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        assert!(parse(&mut b"chat weights".as_slice()).is_err());
+
+        let mut truncated = Builder::default()
+            .string("general.architecture", "llama")
+            .tensor("output_norm.weight", &[4096])
+            .build();
+        truncated.truncate(truncated.len() - 4);
+        assert_eq!(
+            parse(&mut truncated.as_slice()).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
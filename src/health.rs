@@ -1,21 +1,112 @@
 use crate::{
-    error::AssistantError, Interval, ServerLogFile, MAX_TIME_SPAN_IN_SECONDS, SERVER_HEALTH,
-    SERVER_SOCKET_ADDRESS, TIMESTAMP_LAST_ACCESS_LOG,
+    assistant::{Event, EventSender},
+    clock::{SharedClock, Ticker},
+    config::ProbeConfig,
+    consistency::Mismatch,
+    endpoint::ApiServerEndpoint,
+    error::AssistantError,
+    models::ModelKind,
+    ApiClient, ApiKey, HealthStats, Issues, ProbeSettings, ServerHealth, ServerLogFile,
 };
 use chrono::{DateTime, NaiveDateTime, Utc};
 use core::panic;
 use log::{error, info, warn};
 use regex::Regex;
+use reqwest::StatusCode;
+use serde::Serialize;
 use std::{
+    fmt,
     fs::{self, File},
+    future::Future,
     io::{BufReader, Read, Seek, SeekFrom},
     path::Path,
+    pin::Pin,
     str::FromStr,
-    thread::sleep,
-    time::Duration,
+    sync::Arc,
 };
 use tokio::sync::RwLock;
 
+/// Overall health of the API server, from the best to the worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    /// Responding, with minor issues
+    Degraded,
+    Unhealthy,
+}
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthStatus::Healthy => write!(f, "healthy"),
+            HealthStatus::Degraded => write!(f, "degraded"),
+            HealthStatus::Unhealthy => write!(f, "unhealthy"),
+        }
+    }
+}
+
+/// Reason for reporting the API server unhealthy or degraded although it responds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Issue {
+    /// The model file does not match its expected sha256
+    ModelHashMismatch(ModelKind),
+    /// The model file with an expected sha256 is missing or unreadable
+    ModelMissing(ModelKind),
+    /// The server information is inconsistent with `config.json`
+    ConfigMismatch(Mismatch),
+    /// The API server rejects the API key, or requires one
+    AuthMisconfigured,
+}
+impl Issue {
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            Issue::ModelHashMismatch(_) => "model_hash_mismatch",
+            Issue::ModelMissing(_) => "model_missing",
+            Issue::ConfigMismatch(mismatch) => mismatch.reason(),
+            Issue::AuthMisconfigured => "auth_misconfigured",
+        }
+    }
+
+    /// Whether the API server stays healthy, only degraded
+    pub(crate) fn is_degraded(&self) -> bool {
+        matches!(self, Issue::ConfigMismatch(_))
+    }
+}
+
+/// Why the API server is unhealthy, besides the issues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// The API server does not respond
+    Unreachable,
+    /// The API server responds to the probe with a failing vector database
+    ProbeFailed,
+    /// The latest response logged by the API server has a 500 status
+    ResponseFailed,
+}
+impl Failure {
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            Failure::Unreachable => "api_server_unreachable",
+            Failure::ProbeFailed => "probe_failed",
+            Failure::ResponseFailed => "api_server_error",
+        }
+    }
+}
+
+/// What the health checker observed of the API server since the assistant started.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ApiServerStats {
+    // time of the last probe answered with a successful status
+    pub(crate) last_successful_probe: Option<DateTime<Utc>>,
+    // responses found in the log, and those with a 5xx status
+    pub(crate) requests: u64,
+    pub(crate) failed_requests: u64,
+    // since when the API server responds without interruption
+    pub(crate) up_since: Option<DateTime<Utc>>,
+    // why the API server was last found unhealthy, `None` while healthy
+    pub(crate) failure: Option<Failure>,
+}
+
 #[derive(Debug)]
 struct LogMessage {
     timestamp: DateTime<Utc>,
@@ -59,6 +150,11 @@ impl FromStr for LogMessage {
     }
 }
 
+// Whether the API server rejected the request for its API key
+pub(crate) fn is_auth_error(status: StatusCode) -> bool {
+    matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+}
+
 pub(crate) async fn is_file<P: AsRef<Path>>(path: P) -> bool {
     match fs::metadata(path) {
         Ok(metadata) => metadata.is_file(),
@@ -66,560 +162,450 @@ pub(crate) async fn is_file<P: AsRef<Path>>(path: P) -> bool {
     }
 }
 
-pub(crate) async fn check_server_health(
-    log_file: ServerLogFile,
-    interval: Interval,
-) -> Result<(), AssistantError> {
-    info!("Start health checker");
+/// Response of a synthetic request sent to the API server.
+#[derive(Debug, Clone)]
+pub struct ProbeResponse {
+    pub status: StatusCode,
+    /// Body of the response. Only read for unsuccessful responses
+    pub body: String,
+}
 
-    let log_file_path = log_file.read().await;
+/// Sends synthetic requests to the API server.
+pub trait Prober: Send + Sync {
+    fn ping(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<ProbeResponse, AssistantError>> + Send + '_>>;
+}
 
-    let mut file = match File::open(&*log_file_path) {
-        Ok(file) => file,
-        Err(e) => {
-            let err_msg = e.to_string();
+/// Prober that sends a chat completion request to the API server over HTTP.
+#[derive(Debug, Clone)]
+pub struct HttpProber {
+    endpoint: ApiServerEndpoint,
+    config: ProbeSettings,
+    client: ApiClient,
+    api_key: ApiKey,
+}
+impl HttpProber {
+    pub fn new(endpoint: impl Into<ApiServerEndpoint>, config: ProbeConfig) -> Self {
+        let endpoint = endpoint.into();
+        let client = crate::http::api_client(&Default::default(), &endpoint).unwrap_or_default();
+        Self::with_settings(
+            endpoint,
+            Arc::new(RwLock::new(config)),
+            Arc::new(RwLock::new(client)),
+            Arc::new(RwLock::new(None)),
+        )
+    }
 
-            error!("{}", &err_msg);
+    /// Send the requests with the client, see [`crate::http::api_client`]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Arc::new(RwLock::new(client));
+        self
+    }
 
-            return Err(AssistantError::Operation(err_msg));
+    /// Send the API key of the API server as a bearer token, see [`crate::http::api_key`]
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = Arc::new(RwLock::new(api_key));
+        self
+    }
+
+    // the request follows the changes of the shared settings, client and API key
+    pub(crate) fn with_settings(
+        endpoint: ApiServerEndpoint,
+        config: ProbeSettings,
+        client: ApiClient,
+        api_key: ApiKey,
+    ) -> Self {
+        Self {
+            endpoint,
+            config,
+            client,
+            api_key,
         }
-    };
-    let file_clone = match file.try_clone() {
-        Ok(file) => file,
-        Err(_) => {
-            let err_msg = "Unable to clone file handle";
+    }
+}
+impl Prober for HttpProber {
+    fn ping(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<ProbeResponse, AssistantError>> + Send + '_>> {
+        Box::pin(async move {
+            let config = self.config.read().await.clone();
+            let client = self.client.read().await.clone();
+            let api_key = self.api_key.read().await.clone();
+            let response =
+                ping_server(&self.endpoint, &config, &client, api_key.as_deref()).await?;
+
+            let status = response.status();
+            let body = match status.is_success() {
+                true => String::new(),
+                // get the body of the response in string format
+                false => match response.text().await {
+                    Ok(body) => body,
+                    Err(e) => {
+                        error!("Failed to get the body of the response: {}", e);
+                        String::new()
+                    }
+                },
+            };
 
-            error!("{}", err_msg);
+            Ok(ProbeResponse { status, body })
+        })
+    }
+}
 
-            return Err(AssistantError::Operation(err_msg.to_string()));
+/// Periodically checks the health of the API server by scanning its log file, and by
+/// pinging it on the probe schedule if no requests have been logged meanwhile.
+pub(crate) struct HealthChecker {
+    log_file: ServerLogFile,
+    ticker: Ticker,
+    health: ServerHealth,
+    events: EventSender,
+    clock: SharedClock,
+    prober: Arc<dyn Prober>,
+    probe_ticker: Ticker,
+    issues: Issues,
+    stats: HealthStats,
+    // timestamp of the last response
+    last_access: Option<DateTime<Utc>>,
+    // time of the next probe after the last response, None if probing is off
+    next_probe: Option<DateTime<Utc>>,
+}
+impl HealthChecker {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        log_file: ServerLogFile,
+        ticker: Ticker,
+        health: ServerHealth,
+        events: EventSender,
+        clock: SharedClock,
+        prober: Arc<dyn Prober>,
+        probe_ticker: Ticker,
+        issues: Issues,
+        stats: HealthStats,
+    ) -> Self {
+        Self {
+            log_file,
+            ticker,
+            health,
+            events,
+            clock,
+            prober,
+            probe_ticker,
+            issues,
+            stats,
+            last_access: None,
+            next_probe: None,
         }
-    };
-    let mut reader = BufReader::new(file_clone);
-
-    // save the current position of the cursor in the log file
-    let mut current_position = 0;
-    // indicate whether to check the log file. Default is true, so that the log file is checked at the beginning
-    let mut can_check = true;
-    let mut count = 1;
-    loop {
-        info!(
-            ">>>>>>>>>>>>>>>>> Check health ({}) >>>>>>>>>>>>>>>>>",
-            count
-        );
-        count += 1;
+    }
 
-        if can_check {
-            // Start reading from the beginning of the file
-            if let Err(e) = file.seek(SeekFrom::Start(current_position)) {
-                let err_msg = format!("Failed to seek to start of the log file: {}", e);
+    // Record a response, and schedule the next probe from it
+    async fn accessed(&mut self, now: DateTime<Utc>) {
+        self.last_access = Some(now);
+        self.next_probe = self.probe_ticker.next(now).await;
+    }
+
+    pub(crate) async fn run(mut self) -> Result<(), AssistantError> {
+        info!("Start health checker");
+
+        let log_file_path = self.log_file.read().await.clone();
+
+        let mut file = match File::open(&log_file_path) {
+            Ok(file) => file,
+            Err(e) => {
+                let err_msg = e.to_string();
 
                 error!("{}", &err_msg);
 
                 return Err(AssistantError::Operation(err_msg));
             }
+        };
+        let file_clone = match file.try_clone() {
+            Ok(file) => file,
+            Err(_) => {
+                let err_msg = "Unable to clone file handle";
 
-            let mut new_lines = String::new();
-            if let Err(e) = reader.read_to_string(&mut new_lines) {
-                let err_msg = format!("Failed to read log messages from the log file: {}", e);
+                error!("{}", err_msg);
 
-                error!("{}", &err_msg);
+                return Err(AssistantError::Operation(err_msg.to_string()));
+            }
+        };
+        let mut reader = BufReader::new(file_clone);
+
+        // save the current position of the cursor in the log file
+        let mut current_position = 0;
+        // indicate whether to check the log file. Default is true, so that the log file is checked at the beginning
+        let mut can_check = true;
+        let mut count = 1;
+        loop {
+            info!(
+                ">>>>>>>>>>>>>>>>> Check health ({}) >>>>>>>>>>>>>>>>>",
+                count
+            );
+            count += 1;
+
+            if can_check {
+                // Start reading from the beginning of the file
+                if let Err(e) = file.seek(SeekFrom::Start(current_position)) {
+                    let err_msg = format!("Failed to seek to start of the log file: {}", e);
 
-                return Err(AssistantError::Operation(err_msg));
-            };
-            info!("Found {} new log messages", new_lines.lines().count());
-
-            // analyze the log messages and update the server health
-            let mut updated = false;
-            for line in new_lines.lines().rev() {
-                if let Ok(log_message) = LogMessage::from_str(line) {
-                    if log_message.custom_message.starts_with("response_status:") {
-                        // get the status code
-                        let status_code = log_message
-                            .custom_message
-                            .split_whitespace()
-                            .last()
-                            .unwrap()
-                            .to_string();
-                        info!(
-                            "Found the latest response: status: {}, timestamp: {}",
-                            status_code, log_message.timestamp
-                        );
+                    error!("{}", &err_msg);
 
-                        // record the timestamp of the latest response
-                        match TIMESTAMP_LAST_ACCESS_LOG.get() {
-                            Some(timestamp) => {
-                                let mut timestamp = timestamp.write().await;
-
-                                *timestamp = Utc::now();
-                            }
-                            None => {
-                                TIMESTAMP_LAST_ACCESS_LOG
-                                    .set(RwLock::new(Utc::now()))
-                                    .expect("Failed to set TIMESTAMP_LAST_ACCESS_LOG");
-                            }
-                        }
-
-                        if status_code == "500" {
-                            match SERVER_HEALTH.get() {
-                                Some(server_health) => {
-                                    let mut healthy = server_health.write().await;
-
-                                    if *healthy {
-                                        *healthy = false;
-                                    }
-                                }
-                                None => {
-                                    SERVER_HEALTH
-                                        .set(RwLock::new(false))
-                                        .expect("Failed to set SERVER_HEALTH");
-                                }
-                            };
-
-                            info!("Update SERVER_HEALTH to false");
-                        } else {
-                            match SERVER_HEALTH.get() {
-                                Some(server_health) => {
-                                    let mut healthy = server_health.write().await;
-
-                                    if !*healthy {
-                                        *healthy = true;
-                                    }
-                                }
-                                None => {
-                                    SERVER_HEALTH
-                                        .set(RwLock::new(true))
-                                        .expect("Failed to set SERVER_HEALTH");
-                                }
-                            };
-
-                            info!("Update SERVER_HEALTH to true");
-                        }
-
-                        updated = true;
-
-                        break;
-                    }
+                    return Err(AssistantError::Operation(err_msg));
                 }
-            }
 
-            // ping api-server if SERVER_HEALTH is not updated
-            if !updated {
-                info!("Ping API server");
-                match ping_server().await {
-                    Ok(response) => {
-                        if !response.status().is_success() {
-                            warn!("The response returned by the API server is not successful");
-                        }
-
-                        match SERVER_HEALTH.get() {
-                            Some(server_health) => {
-                                let mut healthy = server_health.write().await;
-
-                                if !*healthy {
-                                    *healthy = true;
-                                }
-                            }
-                            None => {
-                                SERVER_HEALTH
-                                    .set(RwLock::new(true))
-                                    .expect("Failed to set SERVER_HEALTH");
-                            }
-                        }
-
-                        info!("Update SERVER_HEALTH to true");
+                let mut new_lines = String::new();
+                if let Err(e) = reader.read_to_string(&mut new_lines) {
+                    let err_msg = format!("Failed to read log messages from the log file: {}", e);
+
+                    error!("{}", &err_msg);
+
+                    return Err(AssistantError::Operation(err_msg));
+                };
+                info!("Found {} new log messages", new_lines.lines().count());
+                self.count_responses(&new_lines).await;
+
+                // analyze the log messages and update the server health
+                match latest_response_status(&new_lines) {
+                    Some(status_code) => {
+                        // record the timestamp of the latest response
+                        let now = self.clock.now();
+                        self.accessed(now).await;
+
+                        let failure = (status_code == "500").then_some(Failure::ResponseFailed);
+                        self.set_health(failure).await;
                     }
-                    Err(AssistantError::ServerDownError(_)) => {
-                        match SERVER_HEALTH.get() {
-                            Some(server_health) => {
-                                let mut healthy = server_health.write().await;
-
-                                if *healthy {
-                                    *healthy = false;
-                                }
-                            }
-                            None => {
-                                SERVER_HEALTH
-                                    .set(RwLock::new(false))
-                                    .expect("Failed to set SERVER_HEALTH");
-                            }
-                        }
-
-                        info!("Update SERVER_HEALTH to false");
+                    // ping api-server if the server health is not updated
+                    None => {
+                        let failure = self.probe().await;
+                        self.set_health(failure).await;
                     }
+                }
+
+                // Get the current position of the cursor in the log file
+                current_position = match file.stream_position() {
+                    Ok(position) => position,
                     Err(e) => {
-                        let err_msg = format!("{}", e);
+                        let err_msg = format!("Unable to get current file position: {}", e);
 
                         error!("{}", &err_msg);
 
-                        if err_msg.contains("Qdrant error:") {
-                            match SERVER_HEALTH.get() {
-                                Some(server_health) => {
-                                    let mut healthy = server_health.write().await;
-
-                                    if *healthy {
-                                        *healthy = false;
-                                    }
-                                }
-                                None => {
-                                    SERVER_HEALTH
-                                        .set(RwLock::new(false))
-                                        .expect("Failed to set SERVER_HEALTH");
-                                }
-                            };
-
-                            info!("Update SERVER_HEALTH to false");
-                        } else {
-                            match SERVER_HEALTH.get() {
-                                Some(server_health) => {
-                                    let mut healthy = server_health.write().await;
-
-                                    if !*healthy {
-                                        *healthy = true;
-                                    }
-                                }
-                                None => {
-                                    SERVER_HEALTH
-                                        .set(RwLock::new(true))
-                                        .expect("Failed to set SERVER_HEALTH");
-                                }
-                            };
-
-                            info!("Update SERVER_HEALTH to true");
-                        }
+                        return Err(AssistantError::Operation(err_msg));
+                    }
+                };
+            } else {
+                info!("Not found new log messages");
+
+                //* If long time no requests coming in, then invoke `ping_server` function to send a request to /v1/chat/completions endpoint */
+                let now = self.clock.now();
+                let stale = match self.last_access {
+                    Some(timestamp) => {
+                        // compute the time slapsed since the last response
+                        let diff = now.signed_duration_since(timestamp).num_seconds();
+                        info!("Time elapsed: {} secs", diff);
+
+                        self.next_probe.is_some_and(|next| now >= next)
                     }
+                    None => true,
+                };
+
+                if stale {
+                    self.accessed(now).await;
+
+                    let failure = self.probe().await;
+                    self.set_health(failure).await;
                 }
             }
 
-            // Get the current position of the cursor in the log file
-            current_position = match file.stream_position() {
+            // print the server health
+            if let Some(health) = *self.health.read().await {
+                info!("Server health: {}", health);
+            }
+
+            // Sleep for seconds specified in the interval
+            self.ticker.wait().await;
+
+            // Check if there are new log entries
+            // Get the end position
+            let latest_position = match file.seek(SeekFrom::End(0)) {
                 Ok(position) => position,
                 Err(e) => {
-                    let err_msg = format!("Unable to get current file position: {}", e);
+                    let err_msg = format!(
+                        "Failed to get the latest position of the cursor in the log file: {}",
+                        e
+                    );
 
                     error!("{}", &err_msg);
 
                     return Err(AssistantError::Operation(err_msg));
                 }
             };
-            // warn!("The position of current cursor: {}", current_position);
-        } else {
-            info!("Not found new log messages");
-
-            //* If long time no requests coming in, then invoke `ping_server` function to send a request to /v1/chat/completions endpoint */
-            match TIMESTAMP_LAST_ACCESS_LOG.get() {
-                Some(timestamp) => {
-                    let diff = {
-                        let timestamp = timestamp.read().await;
-                        let current_timestamp = Utc::now();
-                        current_timestamp
-                            .signed_duration_since(*timestamp)
-                            .num_seconds()
-                    };
-
-                    // compute the time slapsed since the last response
-                    info!("Time elapsed: {} secs", diff);
-
-                    // if the difference is greater than 60 seconds, send a request to the API server
-                    if diff >= MAX_TIME_SPAN_IN_SECONDS {
-                        // update TIMESTAMP_LAST_ACCESS_LOG
-                        let mut timestamp = timestamp.write().await;
-                        *timestamp = Utc::now();
-
-                        info!("Ping API server");
-                        match ping_server().await {
-                            Ok(response) => {
-                                if !response.status().is_success() {
-                                    warn!(
-                                        "The response returned by the API server is not successful"
-                                    );
-
-                                    // get the body of the response in string format
-                                    match response.text().await {
-                                        Ok(body_text) => {
-                                            let err_msg = body_text;
-
-                                            warn!("{}", &err_msg);
-
-                                            if err_msg.contains("Qdrant error:") {
-                                                match SERVER_HEALTH.get() {
-                                                    Some(server_health) => {
-                                                        let mut healthy =
-                                                            server_health.write().await;
-
-                                                        if *healthy {
-                                                            *healthy = false;
-                                                        }
-                                                    }
-                                                    None => {
-                                                        SERVER_HEALTH
-                                                            .set(RwLock::new(false))
-                                                            .expect("Failed to set SERVER_HEALTH");
-                                                    }
-                                                };
-
-                                                info!("Update SERVER_HEALTH to false");
-                                            } else {
-                                                match SERVER_HEALTH.get() {
-                                                    Some(server_health) => {
-                                                        let mut healthy =
-                                                            server_health.write().await;
-
-                                                        if !*healthy {
-                                                            *healthy = true;
-                                                        }
-                                                    }
-                                                    None => {
-                                                        SERVER_HEALTH
-                                                            .set(RwLock::new(true))
-                                                            .expect("Failed to set SERVER_HEALTH");
-                                                    }
-                                                };
-
-                                                info!("Update SERVER_HEALTH to true");
-                                            }
-                                        }
-                                        Err(e) => {
-                                            let err_msg = format!(
-                                                "Failed to get the body of the response: {}",
-                                                e
-                                            );
-
-                                            error!("{}", &err_msg);
-                                        }
-                                    }
-                                } else {
-                                    match SERVER_HEALTH.get() {
-                                        Some(server_health) => {
-                                            let mut healthy = server_health.write().await;
-
-                                            if !*healthy {
-                                                *healthy = true;
-                                            }
-                                        }
-                                        None => {
-                                            SERVER_HEALTH
-                                                .set(RwLock::new(true))
-                                                .expect("Failed to set SERVER_HEALTH");
-                                        }
-                                    }
-
-                                    info!("Update SERVER_HEALTH to true");
-                                }
-                            }
-                            Err(AssistantError::ServerDownError(err_msg)) => {
-                                error!("{}", &err_msg);
-
-                                match SERVER_HEALTH.get() {
-                                    Some(server_health) => {
-                                        let mut healthy = server_health.write().await;
-
-                                        if *healthy {
-                                            *healthy = false;
-                                        }
-                                    }
-                                    None => {
-                                        SERVER_HEALTH
-                                            .set(RwLock::new(false))
-                                            .expect("Failed to set SERVER_HEALTH");
-                                    }
-                                }
-
-                                info!("Update SERVER_HEALTH to false");
-                            }
-                            Err(e) => {
-                                let err_msg = format!("{}", e);
-
-                                error!("{}", &err_msg);
-
-                                if err_msg.contains("Qdrant error:") {
-                                    match SERVER_HEALTH.get() {
-                                        Some(server_health) => {
-                                            let mut healthy = server_health.write().await;
-
-                                            if *healthy {
-                                                *healthy = false;
-                                            }
-                                        }
-                                        None => {
-                                            SERVER_HEALTH
-                                                .set(RwLock::new(false))
-                                                .expect("Failed to set SERVER_HEALTH");
-                                        }
-                                    };
-
-                                    info!("Update SERVER_HEALTH to false");
-                                } else {
-                                    match SERVER_HEALTH.get() {
-                                        Some(server_health) => {
-                                            let mut healthy = server_health.write().await;
-
-                                            if !*healthy {
-                                                *healthy = true;
-                                            }
-                                        }
-                                        None => {
-                                            SERVER_HEALTH
-                                                .set(RwLock::new(true))
-                                                .expect("Failed to set SERVER_HEALTH");
-                                        }
-                                    };
-
-                                    info!("Update SERVER_HEALTH to true");
-                                }
-                            }
-                        }
-                    }
+
+            // Check if there are new log entries
+            can_check = latest_position > current_position;
+
+            // seek back to the last position for the next iteration
+            if let Err(e) = file.seek(SeekFrom::Start(current_position)) {
+                let err_msg = format!("Failed to set back the cursor to the last position: {}", e);
+
+                error!("{}", &err_msg);
+
+                return Err(AssistantError::Operation(err_msg));
+            }
+        }
+    }
+
+    // Ping the API server and derive the server health from the result: why the server is
+    // unhealthy, `None` if healthy
+    async fn probe(&self) -> Option<Failure> {
+        info!("Ping API server");
+        let result = self.prober.ping().await;
+        match &result {
+            Ok(response) if !response.status.is_success() => {
+                warn!("The response returned by the API server is not successful");
+                warn!("{}", &response.body);
+            }
+            Ok(_) => {}
+            Err(AssistantError::ServerDownError(err_msg)) => error!("{}", err_msg),
+            Err(e) => error!("{}", e),
+        }
+
+        let now = self.clock.now();
+        let mut stats = self.stats.write().await;
+        match &result {
+            Ok(response) => {
+                if response.status.is_success() {
+                    stats.last_successful_probe = Some(now);
                 }
-                None => {
-                    // update TIMESTAMP_LAST_ACCESS_LOG
-                    TIMESTAMP_LAST_ACCESS_LOG
-                        .set(RwLock::new(Utc::now()))
-                        .expect("Failed to set TIMESTAMP_LAST_ACCESS_LOG");
-
-                    info!("Ping API server");
-                    match ping_server().await {
-                        Ok(response) => {
-                            if !response.status().is_success() {
-                                warn!("The response returned by the API server is not successful");
-                            }
-
-                            match SERVER_HEALTH.get() {
-                                Some(server_health) => {
-                                    let mut healthy = server_health.write().await;
-
-                                    if !*healthy {
-                                        *healthy = true;
-                                    }
-                                }
-                                None => {
-                                    SERVER_HEALTH
-                                        .set(RwLock::new(true))
-                                        .expect("Failed to set SERVER_HEALTH");
-                                }
-                            }
-
-                            info!("Update SERVER_HEALTH to true");
-                        }
-                        Err(AssistantError::ServerDownError(_)) => {
-                            match SERVER_HEALTH.get() {
-                                Some(server_health) => {
-                                    let mut healthy = server_health.write().await;
-
-                                    if *healthy {
-                                        *healthy = false;
-                                    }
-                                }
-                                None => {
-                                    SERVER_HEALTH
-                                        .set(RwLock::new(false))
-                                        .expect("Failed to set SERVER_HEALTH");
-                                }
-                            }
-
-                            info!("Update SERVER_HEALTH to false");
-                        }
-                        Err(e) => {
-                            let err_msg = format!("{}", e);
-
-                            error!("{}", &err_msg);
-
-                            if err_msg.contains("Qdrant error:") {
-                                match SERVER_HEALTH.get() {
-                                    Some(server_health) => {
-                                        let mut healthy = server_health.write().await;
-
-                                        if *healthy {
-                                            *healthy = false;
-                                        }
-                                    }
-                                    None => {
-                                        SERVER_HEALTH
-                                            .set(RwLock::new(false))
-                                            .expect("Failed to set SERVER_HEALTH");
-                                    }
-                                };
-
-                                info!("Update SERVER_HEALTH to false");
-                            } else {
-                                match SERVER_HEALTH.get() {
-                                    Some(server_health) => {
-                                        let mut healthy = server_health.write().await;
-
-                                        if !*healthy {
-                                            *healthy = true;
-                                        }
-                                    }
-                                    None => {
-                                        SERVER_HEALTH
-                                            .set(RwLock::new(true))
-                                            .expect("Failed to set SERVER_HEALTH");
-                                    }
-                                };
-
-                                info!("Update SERVER_HEALTH to true");
-                            }
-                        }
-                    }
+                stats.up_since.get_or_insert(now);
+            }
+            Err(AssistantError::ServerDownError(_)) => stats.up_since = None,
+            Err(_) => {}
+        }
+        drop(stats);
+
+        // the probe tells whether the API key is accepted
+        if let Ok(response) = &result {
+            let mut issues = self.issues.write().await;
+            match is_auth_error(response.status) {
+                true => {
+                    error!("The API server rejected the probe, check the API key");
+                    issues.insert(Issue::AuthMisconfigured);
+                }
+                false => {
+                    issues.remove(&Issue::AuthMisconfigured);
                 }
             }
         }
 
-        // print the server health
-        if let Some(health) = SERVER_HEALTH.get() {
-            let health = health.read().await;
-            info!("Server health: {}", *health);
+        match (is_healthy(&result), &result) {
+            (true, _) => None,
+            (false, Err(AssistantError::ServerDownError(_))) => Some(Failure::Unreachable),
+            (false, _) => Some(Failure::ProbeFailed),
         }
+    }
 
-        // Sleep for seconds specified in the interval
-        let interval = interval.read().await;
-        sleep(Duration::from_secs(*interval));
+    async fn set_health(&self, failure: Option<Failure>) {
+        self.stats.write().await.failure = failure;
+        update_health(&self.health, &self.events, failure.is_none()).await;
+    }
 
-        // Check if there are new log entries
-        // Get the end position
-        let latest_position = match file.seek(SeekFrom::End(0)) {
-            Ok(position) => position,
-            Err(e) => {
-                let err_msg = format!(
-                    "Failed to get the latest position of the cursor in the log file: {}",
-                    e
-                );
+    // Count the responses logged by the API server, which is up if it logs any
+    async fn count_responses(&self, log_lines: &str) {
+        let statuses = response_statuses(log_lines);
+        if statuses.is_empty() {
+            return;
+        }
+        let now = self.clock.now();
+        let mut stats = self.stats.write().await;
+        stats.requests += statuses.len() as u64;
+        stats.failed_requests += statuses.iter().filter(|status| **status >= 500).count() as u64;
+        stats.up_since.get_or_insert(now);
+    }
+}
 
-                error!("{}", &err_msg);
+// Derive the server health from the result of a probe. Only an unreachable server and a
+// failing vector database make the server unhealthy
+pub(crate) fn is_healthy(result: &Result<ProbeResponse, AssistantError>) -> bool {
+    match result {
+        Ok(response) => response.status.is_success() || !response.body.contains("Qdrant error:"),
+        Err(AssistantError::ServerDownError(_)) => false,
+        Err(e) => !e.to_string().contains("Qdrant error:"),
+    }
+}
 
-                return Err(AssistantError::Operation(err_msg));
-            }
-        };
+// Store the server health, and emit an event if it changed
+pub(crate) async fn update_health(health: &ServerHealth, events: &EventSender, healthy: bool) {
+    let mut health = health.write().await;
+    let changed = *health != Some(healthy);
+    *health = Some(healthy);
 
-        // Check if there are new log entries
-        can_check = latest_position > current_position;
+    info!("Update server health to {}", healthy);
 
-        // seek back to the last position for the next iteration
-        if let Err(e) = file.seek(SeekFrom::Start(current_position)) {
-            let err_msg = format!("Failed to set back the cursor to the last position: {}", e);
+    if changed {
+        let _ = events.send(Event::HealthChanged { healthy });
+    }
+}
 
-            error!("{}", &err_msg);
+// Find the status code of the latest response in the log messages
+fn latest_response_status(log_lines: &str) -> Option<String> {
+    for line in log_lines.lines().rev() {
+        if let Ok(log_message) = LogMessage::from_str(line) {
+            if log_message.custom_message.starts_with("response_status:") {
+                // get the status code
+                let status_code = log_message
+                    .custom_message
+                    .split_whitespace()
+                    .last()
+                    .unwrap()
+                    .to_string();
+                info!(
+                    "Found the latest response: status: {}, timestamp: {}",
+                    status_code, log_message.timestamp
+                );
 
-            return Err(AssistantError::Operation(err_msg));
+                return Some(status_code);
+            }
         }
     }
+
+    None
+}
+
+// Status codes of the responses in the log messages
+fn response_statuses(log_lines: &str) -> Vec<u16> {
+    log_lines
+        .lines()
+        .filter_map(|line| LogMessage::from_str(line).ok())
+        .filter_map(|log_message| {
+            log_message
+                .custom_message
+                .strip_prefix("response_status:")
+                .and_then(|status| status.trim().parse().ok())
+        })
+        .collect()
 }
 
 // Send a request to the LlamaEdge API Server
-async fn ping_server() -> Result<reqwest::Response, AssistantError> {
-    let addr = SERVER_SOCKET_ADDRESS.get().unwrap().read().await;
-    let addr = (*addr).to_string();
-    let url = format!("http://{}{}", addr, "/v1/chat/completions");
-
-    let client = reqwest::Client::new();
-    match client
-        .post(&url)
+async fn ping_server(
+    endpoint: &ApiServerEndpoint,
+    config: &ProbeConfig,
+    client: &reqwest::Client,
+    api_key: Option<&str>,
+) -> Result<reqwest::Response, AssistantError> {
+    let url = endpoint.url("/v1/chat/completions");
+
+    let mut request = client.post(&url);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    match request
         .header("Content-Type", "application/json")
         .json(&serde_json::json!({
             "messages": [{
                 "role": "user",
-                "content": "Who are you? <server-health>"
+                "content": config.prompt
             }],
-            "model": "Phi-3-mini-4k-instruct",
+            "model": config.model,
             "stream": false
         }))
         .send()
@@ -638,3 +624,351 @@ async fn ping_server() -> Result<reqwest::Response, AssistantError> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{Clock, TokioClock},
+        config::AssistantConfig,
+        schedule::{Schedules, Task},
+    };
+    use chrono::TimeZone;
+    use std::time::Duration;
+    use std::{
+        io::Write,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+    use tempfile::TempDir;
+    use tokio::{sync::Notify, task::JoinHandle, time::sleep};
+
+    const INTERVAL: u64 = 10;
+
+    struct ScriptedProber {
+        outcome: Mutex<Result<ProbeResponse, AssistantError>>,
+        calls: AtomicUsize,
+    }
+    impl ScriptedProber {
+        fn set(&self, outcome: Result<ProbeResponse, AssistantError>) {
+            *self.outcome.lock().unwrap() = outcome;
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+    impl Prober for ScriptedProber {
+        fn ping(
+            &self,
+        ) -> Pin<Box<dyn Future<Output = Result<ProbeResponse, AssistantError>> + Send + '_>>
+        {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let outcome = self.outcome.lock().unwrap().clone();
+            Box::pin(async move { outcome })
+        }
+    }
+
+    fn respond(status: u16, body: &str) -> Result<ProbeResponse, AssistantError> {
+        Ok(ProbeResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: body.to_string(),
+        })
+    }
+
+    fn server_down() -> Result<ProbeResponse, AssistantError> {
+        Err(AssistantError::ServerDownError(
+            "error sending request".to_string(),
+        ))
+    }
+
+    struct Fixture {
+        _dir: TempDir,
+        log_file: PathBuf,
+        health: ServerHealth,
+        events: EventSender,
+        prober: Arc<ScriptedProber>,
+        issues: Issues,
+        stats: HealthStats,
+        clock: Arc<TokioClock>,
+        handle: JoinHandle<Result<(), AssistantError>>,
+    }
+    impl Fixture {
+        fn start(log_lines: &[u16]) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let log_file = dir.path().join("start-llamaedge.log");
+            File::create(&log_file).unwrap();
+
+            let clock = Arc::new(TokioClock::new(
+                Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap(),
+            ));
+            let health: ServerHealth = Arc::new(RwLock::new(None));
+            let prober = Arc::new(ScriptedProber {
+                outcome: Mutex::new(respond(200, "")),
+                calls: AtomicUsize::new(0),
+            });
+
+            let (events, _) = tokio::sync::broadcast::channel(16);
+
+            let mut fixture = Self {
+                _dir: dir,
+                log_file,
+                health,
+                events,
+                prober,
+                issues: Default::default(),
+                stats: Default::default(),
+                clock,
+                handle: tokio::spawn(async { Ok(()) }),
+            };
+            for status in log_lines {
+                fixture.log_response(*status);
+            }
+
+            let schedules = Arc::new(RwLock::new(Schedules::new(&AssistantConfig {
+                interval: INTERVAL,
+                ..Default::default()
+            })));
+            let ticker = |task| {
+                Ticker::new(
+                    Arc::clone(&schedules),
+                    task,
+                    Arc::new(Notify::new()),
+                    fixture.clock.clone(),
+                )
+            };
+            let checker = HealthChecker::new(
+                Arc::new(RwLock::new(fixture.log_file.to_string_lossy().to_string())),
+                ticker(Task::LogScan),
+                Arc::clone(&fixture.health),
+                fixture.events.clone(),
+                fixture.clock.clone(),
+                fixture.prober.clone(),
+                ticker(Task::Probe),
+                Arc::clone(&fixture.issues),
+                Arc::clone(&fixture.stats),
+            );
+            fixture.handle = tokio::spawn(checker.run());
+
+            fixture
+        }
+
+        // append a response record in the format written by LlamaEdge API Server
+        fn log_response(&self, status: u16) {
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .open(&self.log_file)
+                .unwrap();
+            writeln!(
+                file,
+                "[{}] [info] llama_api_server in llama-api-server/src/main.rs:520: response_status: {}",
+                self.clock.now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                status
+            )
+            .unwrap();
+        }
+
+        async fn health(&self) -> Option<bool> {
+            *self.health.read().await
+        }
+    }
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            self.handle.abort();
+        }
+    }
+
+    #[test]
+    fn parse_log_message() {
+        let line = "[2024-08-01 10:20:30.456] [info] llama_api_server in llama-api-server/src/main.rs:520: response_status: 200";
+        let message = LogMessage::from_str(line).unwrap();
+        assert_eq!(
+            message.timestamp,
+            Utc.with_ymd_and_hms(2024, 8, 1, 10, 20, 30).unwrap()
+                + chrono::Duration::milliseconds(456)
+        );
+        assert_eq!(message.custom_message, "response_status: 200");
+
+        assert!(LogMessage::from_str("not a log message").is_err());
+    }
+
+    #[test]
+    fn latest_response_wins() {
+        let lines = "[2024-08-01 10:20:30.000] [info] llama_api_server in src/main.rs:1: response_status: 500\n\
+                     [2024-08-01 10:20:31.000] [info] llama_api_server in src/main.rs:1: response_status: 200\n\
+                     [2024-08-01 10:20:32.000] [info] llama_api_server in src/main.rs:1: request_id: 42\n";
+        assert_eq!(latest_response_status(lines), Some("200".to_string()));
+        assert_eq!(latest_response_status("garbage\n"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn logged_responses_drive_health_without_probing() {
+        let fixture = Fixture::start(&[200]);
+
+        sleep(Duration::from_secs(5)).await;
+        assert_eq!(fixture.health().await, Some(true));
+
+        fixture.log_response(500);
+        sleep(Duration::from_secs(10)).await;
+        assert_eq!(fixture.health().await, Some(false));
+
+        fixture.log_response(200);
+        sleep(Duration::from_secs(10)).await;
+        assert_eq!(fixture.health().await, Some(true));
+
+        assert_eq!(fixture.prober.calls(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stats_follow_responses_and_probes() {
+        let fixture = Fixture::start(&[200, 500]);
+        let origin = fixture.clock.now();
+        let at = |secs| origin + chrono::Duration::seconds(secs);
+
+        sleep(Duration::from_secs(5)).await;
+        assert_eq!(
+            *fixture.stats.read().await,
+            ApiServerStats {
+                last_successful_probe: None,
+                requests: 2,
+                failed_requests: 1,
+                up_since: Some(at(0)),
+                failure: Some(Failure::ResponseFailed),
+            }
+        );
+
+        // probed at 30s, then 60s
+        fixture.prober.set(server_down());
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(fixture.stats.read().await.up_since, None);
+        assert_eq!(
+            fixture.stats.read().await.failure,
+            Some(Failure::Unreachable)
+        );
+
+        fixture.prober.set(respond(200, ""));
+        sleep(Duration::from_secs(30)).await;
+        let stats = fixture.stats.read().await.clone();
+        assert_eq!(stats.last_successful_probe, Some(at(60)));
+        assert_eq!(stats.up_since, Some(at(60)));
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.failure, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn probe_results_map_to_health() {
+        // with an empty log the server is pinged at 0s, 10s, 40s, 70s, 100s, ...
+        let fixture = Fixture::start(&[]);
+
+        sleep(Duration::from_secs(5)).await;
+        assert_eq!(fixture.health().await, Some(true));
+
+        fixture.prober.set(server_down());
+        sleep(Duration::from_secs(10)).await;
+        assert_eq!(fixture.health().await, Some(false));
+
+        fixture.prober.set(respond(200, ""));
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(fixture.health().await, Some(true));
+
+        fixture
+            .prober
+            .set(respond(500, "Qdrant error: collection not found"));
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(fixture.health().await, Some(false));
+
+        fixture.prober.set(respond(503, "server busy"));
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(fixture.health().await, Some(true));
+
+        assert_eq!(fixture.prober.calls(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejected_probes_report_auth_misconfigured() {
+        let fixture = Fixture::start(&[]);
+        fixture.prober.set(respond(401, "Invalid API key"));
+
+        sleep(Duration::from_secs(5)).await;
+        assert!(fixture
+            .issues
+            .read()
+            .await
+            .contains(&Issue::AuthMisconfigured));
+
+        fixture.prober.set(respond(200, ""));
+        sleep(Duration::from_secs(10)).await;
+        assert!(fixture.issues.read().await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn health_changes_are_emitted_once() {
+        let fixture = Fixture::start(&[200]);
+        let mut events = fixture.events.subscribe();
+
+        sleep(Duration::from_secs(5)).await;
+        for status in [200, 500, 500, 200] {
+            fixture.log_response(status);
+            sleep(Duration::from_secs(10)).await;
+        }
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                Event::HealthChanged { healthy: true },
+                Event::HealthChanged { healthy: false },
+                Event::HealthChanged { healthy: true },
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_server_is_pinged_once_per_max_time_span() {
+        let fixture = Fixture::start(&[]);
+
+        sleep(Duration::from_secs(3 * 3600 + 5)).await;
+
+        // pings at 0s and 10s, then every MAX_TIME_SPAN_IN_SECONDS up to 10780s
+        assert_eq!(fixture.prober.calls(), 2 + 359);
+        assert_eq!(fixture.health().await, Some(true));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hours_of_traffic_followed_by_outage() {
+        let fixture = Fixture::start(&[]);
+        fixture.prober.set(server_down());
+
+        // two hours of traffic with a request every 20 seconds, failing between 1h and 1h10m
+        sleep(Duration::from_secs(5)).await;
+        let calls_before_traffic = fixture.prober.calls();
+        for i in 0..360u64 {
+            let elapsed = 5 + i * 20;
+            let status = match (3600..4200).contains(&elapsed) {
+                true => 500,
+                false => 200,
+            };
+            fixture.log_response(status);
+            sleep(Duration::from_secs(20)).await;
+
+            match elapsed {
+                3900 => assert_eq!(fixture.health().await, Some(false)),
+                4500 => assert_eq!(fixture.health().await, Some(true)),
+                _ => {}
+            }
+        }
+        assert_eq!(fixture.prober.calls(), calls_before_traffic);
+        assert_eq!(fixture.health().await, Some(true));
+
+        // the server goes silent and is pinged once the last response is older than the threshold
+        sleep(Duration::from_secs(4 * 3600)).await;
+        assert_eq!(fixture.health().await, Some(false));
+        assert!(fixture.prober.calls() > calls_before_traffic + 400);
+    }
+}
@@ -0,0 +1,227 @@
+//! HTTP client shared by the requests to the API server, the hub and the subscribers.
+
+use crate::{
+    config::{AssistantConfig, HttpConfig},
+    endpoint::ApiServerEndpoint,
+    error::AssistantError,
+};
+use log::{error, info};
+use reqwest::{Certificate, Client, ClientBuilder, Identity, NoProxy, Proxy};
+use std::{path::Path, time::Duration};
+
+/// Build the HTTP client from the `[http]` settings: the CA certificates trusted besides the
+/// built-in roots, the client certificate, the proxy and the timeouts.
+pub fn client(config: &HttpConfig) -> Result<Client, AssistantError> {
+    build(builder(config)?)
+}
+
+/// Build the HTTP client of the requests to the API server: the client of [`client`],
+/// connecting to the Unix domain socket of the endpoint, if any.
+pub fn api_client(
+    config: &HttpConfig,
+    endpoint: &ApiServerEndpoint,
+) -> Result<Client, AssistantError> {
+    let builder = builder(config)?;
+    match endpoint.unix_socket() {
+        #[cfg(unix)]
+        Some(path) => {
+            info!("Unix domain socket of the API server: {}", path.display());
+            build(builder.unix_socket(path))
+        }
+        #[cfg(not(unix))]
+        Some(_) => {
+            let err_msg = "Unix domain sockets are not supported on this platform".to_string();
+            error!("{}", &err_msg);
+            Err(AssistantError::ConfigError(err_msg))
+        }
+        None => build(builder),
+    }
+}
+
+fn builder(config: &HttpConfig) -> Result<ClientBuilder, AssistantError> {
+    let mut builder = Client::builder();
+    if config.connect_timeout > 0 {
+        builder = builder.connect_timeout(Duration::from_secs(config.connect_timeout));
+    }
+    if config.timeout > 0 {
+        builder = builder.timeout(Duration::from_secs(config.timeout));
+    }
+
+    if let Some(path) = &config.ca_cert {
+        let certs = Certificate::from_pem_bundle(&read(path)?).map_err(|e| invalid(path, e))?;
+        if certs.is_empty() {
+            return Err(invalid(path, "no certificate found"));
+        }
+        info!("Trust the CA certificates of {}", path.display());
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            // the certificate chain and the key are read as a single PEM
+            let mut pem = read(cert)?;
+            pem.push(b'\n');
+            pem.extend(read(key)?);
+            let identity = Identity::from_pem(&pem).map_err(|e| invalid(cert, e))?;
+            info!("Client certificate: {}", cert.display());
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            let err_msg = "client_cert and client_key must be set together".to_string();
+            error!("{}", &err_msg);
+            return Err(AssistantError::ConfigError(err_msg));
+        }
+    }
+
+    if let Some(url) = &config.proxy {
+        let proxy = Proxy::all(url).map_err(|e| {
+            let err_msg = format!("Invalid proxy {}: {}", url, e);
+            error!("{}", &err_msg);
+            AssistantError::ConfigError(err_msg)
+        })?;
+        info!("Proxy: {}, except for {}", url, &config.no_proxy);
+        builder = builder.proxy(proxy.no_proxy(NoProxy::from_string(&config.no_proxy)));
+    }
+
+    Ok(builder)
+}
+
+fn build(builder: ClientBuilder) -> Result<Client, AssistantError> {
+    builder.build().map_err(|e| {
+        let err_msg = format!("Failed to create the HTTP client: {}", e);
+        error!("{}", &err_msg);
+        AssistantError::ConfigError(err_msg)
+    })
+}
+
+/// API key of the API server: `api_key` from the configuration or `GAIAS_API_KEY`, else the
+/// content of `paths.api_key` in the gaianet directory, if the file exists.
+pub fn api_key(
+    config: &AssistantConfig,
+    gaianet_dir: &Path,
+) -> Result<Option<String>, AssistantError> {
+    if let Some(key) = &config.api_key {
+        return Ok(Some(key.trim().to_string()).filter(|key| !key.is_empty()));
+    }
+
+    let path = gaianet_dir.join(&config.paths.api_key);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = String::from_utf8(read(&path)?).map_err(|e| invalid(&path, e))?;
+    info!("API key of the API server: {}", path.display());
+    Ok(Some(content.trim().to_string()).filter(|key| !key.is_empty()))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, AssistantError> {
+    std::fs::read(path).map_err(|e| {
+        let err_msg = format!("Failed to read {}: {}", path.display(), e);
+        error!("{}", &err_msg);
+        AssistantError::ConfigError(err_msg)
+    })
+}
+
+fn invalid(path: &Path, e: impl std::fmt::Display) -> AssistantError {
+    let err_msg = format!("Invalid {}: {}", path.display(), e);
+    error!("{}", &err_msg);
+    AssistantError::ConfigError(err_msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_client_is_built() {
+        assert!(client(&HttpConfig::default()).is_ok());
+
+        let config = HttpConfig {
+            proxy: Some("socks5h://proxy.internal:1080".to_string()),
+            ..Default::default()
+        };
+        assert!(client(&config).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn api_client_connects_to_the_unix_socket() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("llamaedge.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let n = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request[..n]).to_string()
+        });
+
+        let endpoint = ApiServerEndpoint::Unix(path);
+        let client = api_client(&HttpConfig::default(), &endpoint).unwrap();
+        let response = client.get(endpoint.url("/v1/info")).send().await.unwrap();
+
+        assert!(response.status().is_success());
+        assert!(server.await.unwrap().starts_with("GET /v1/info HTTP/1.1"));
+    }
+
+    #[test]
+    fn api_key_is_read_from_config_or_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = AssistantConfig::default();
+        assert_eq!(api_key(&config, dir.path()).unwrap(), None);
+
+        std::fs::write(dir.path().join("api-key"), "gaia-0123456789\n").unwrap();
+        assert_eq!(
+            api_key(&config, dir.path()).unwrap().as_deref(),
+            Some("gaia-0123456789")
+        );
+
+        config.api_key = Some("gaia-abcdef".to_string());
+        assert_eq!(
+            api_key(&config, dir.path()).unwrap().as_deref(),
+            Some("gaia-abcdef")
+        );
+    }
+
+    #[test]
+    fn invalid_settings_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let pem = dir.path().join("ca.pem");
+        std::fs::write(&pem, "not a certificate").unwrap();
+
+        let config = HttpConfig {
+            ca_cert: Some(pem.clone()),
+            ..Default::default()
+        };
+        let err = client(&config).unwrap_err().to_string();
+        assert!(
+            err.starts_with(&format!("Invalid {}", pem.display())),
+            "{}",
+            err
+        );
+
+        let config = HttpConfig {
+            client_cert: Some(pem),
+            ..Default::default()
+        };
+        assert_eq!(
+            client(&config).unwrap_err().to_string(),
+            "client_cert and client_key must be set together"
+        );
+
+        let config = HttpConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        let err = client(&config).unwrap_err().to_string();
+        assert!(err.starts_with("Invalid proxy not a url"), "{}", err);
+    }
+}
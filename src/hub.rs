@@ -0,0 +1,178 @@
+use crate::{
+    config::{HubConfig, Topic},
+    gaianet::NodeConfig,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::net::IpAddr;
+
+// placeholders left after the known ones are replaced
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{[^{}]*\}").unwrap());
+
+// path appended to the hub URLs without `{topic}`
+const DEFAULT_PATH: &str = "/device-{topic}/{device_id}";
+
+// URLs of the hubs subscribed to the server information and health
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HubUrls {
+    pub(crate) info: Vec<String>,
+    pub(crate) health: Vec<String>,
+}
+impl HubUrls {
+    // Expand the URL templates of the hubs for the node
+    pub(crate) fn new(hub: &HubConfig, node: &NodeConfig) -> Result<Self, String> {
+        let mut urls = Self {
+            info: Vec::new(),
+            health: Vec::new(),
+        };
+        for template in std::iter::once(&hub.url).chain(hub.extra_urls.iter()) {
+            urls.info
+                .push(expand(template, node, Topic::Info, hub.allow_http)?);
+            urls.health
+                .push(expand(template, node, Topic::Health, hub.allow_http)?);
+        }
+        Ok(urls)
+    }
+}
+
+// Replace the placeholders of the template, and check that the URL is usable
+fn expand(
+    template: &str,
+    node: &NodeConfig,
+    topic: Topic,
+    allow_http: bool,
+) -> Result<String, String> {
+    let template = match template.contains("{topic}") {
+        true => template.to_string(),
+        false => format!("{}{}", template.trim_end_matches('/'), DEFAULT_PATH),
+    };
+    let topic = match topic {
+        Topic::Info => "info",
+        Topic::Health => "health",
+    };
+    let url = template
+        .replace("{domain}", &node.config.domain)
+        .replace("{device_id}", node.device_id())
+        .replace("{topic}", topic);
+    if let Some(placeholder) = PLACEHOLDER.find(&url) {
+        return Err(format!(
+            "{}: unknown placeholder {}, expected {{domain}}, {{device_id}} or {{topic}}",
+            template,
+            placeholder.as_str()
+        ));
+    }
+
+    let parsed = reqwest::Url::parse(&url).map_err(|e| format!("{}: {}", url, e))?;
+    let host = parsed.host_str().unwrap_or_default();
+    if host.is_empty() {
+        return Err(format!("{}: missing host", url));
+    }
+    match parsed.scheme() {
+        "https" => {}
+        "http" if allow_http || is_loopback(host) => {}
+        "http" => {
+            return Err(format!(
+                "{}: plain http is only allowed for loopback hosts, unless allow_http is set",
+                url
+            ))
+        }
+        _ => return Err(format!("{}: not an http(s) URL", url)),
+    }
+
+    Ok(url)
+}
+
+fn is_loopback(host: &str) -> bool {
+    host == "localhost"
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gaianet::{FrpcConfig, FrpcMetadatas, GaianetConfig};
+
+    fn node() -> NodeConfig {
+        NodeConfig {
+            config: GaianetConfig {
+                domain: "gaia.domains".to_string(),
+                ..Default::default()
+            },
+            frpc: FrpcConfig {
+                metadatas: FrpcMetadatas {
+                    device_id: "device-1".to_string(),
+                },
+            },
+            server_log_file: Default::default(),
+        }
+    }
+
+    fn hub(url: &str) -> HubConfig {
+        HubConfig {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn base_urls_get_the_default_path() {
+        let urls = HubUrls::new(&HubConfig::default(), &node()).unwrap();
+
+        assert_eq!(
+            urls.info,
+            ["https://hub.domain.gaia.domains/device-info/device-1"]
+        );
+        assert_eq!(
+            urls.health,
+            ["https://hub.domain.gaia.domains/device-health/device-1"]
+        );
+    }
+
+    #[test]
+    fn templates_are_expanded_for_all_hubs() {
+        let mut hub = hub("https://staging.{domain}/v2/nodes/{device_id}/{topic}");
+        hub.extra_urls = vec!["http://localhost:3000/".to_string()];
+
+        let urls = HubUrls::new(&hub, &node()).unwrap();
+
+        assert_eq!(
+            urls.health,
+            [
+                "https://staging.gaia.domains/v2/nodes/device-1/health",
+                "http://localhost:3000/device-health/device-1"
+            ]
+        );
+        assert_eq!(
+            urls.info[0],
+            "https://staging.gaia.domains/v2/nodes/device-1/info"
+        );
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        let err = HubUrls::new(&hub("https://hub.{domian}"), &node()).unwrap_err();
+        assert!(err.contains("unknown placeholder {domian}"), "{}", err);
+
+        let err = HubUrls::new(&hub("hub.{domain}"), &node()).unwrap_err();
+        assert!(
+            err.starts_with("hub.gaia.domains/device-info/device-1: "),
+            "{}",
+            err
+        );
+        let err = HubUrls::new(&hub("ftp://hub.{domain}"), &node()).unwrap_err();
+        assert!(err.ends_with("not an http(s) URL"), "{}", err);
+
+        // plain http only for local development
+        let err = HubUrls::new(&hub("http://hub.{domain}"), &node()).unwrap_err();
+        assert!(err.contains("plain http"), "{}", err);
+        assert!(HubUrls::new(&hub("http://[::1]:3000"), &node()).is_ok());
+        let mut hub = hub("http://hub.{domain}");
+        hub.allow_http = true;
+        assert!(HubUrls::new(&hub, &node()).is_ok());
+    }
+}
@@ -0,0 +1,451 @@
+//! Identity of the node: the payloads pushed to the subscribers are signed with the key of
+//! the node, so that the hub can check that they come from the node they claim to.
+//!
+//! The key is read from `nodeid.json` in the gaianet directory, which gives the address of
+//! the node, the Ethereum keystore (v3) holding its secp256k1 key and the keystore password.
+//! A payload is signed as an Ethereum personal message (EIP-191) over
+//! `{timestamp}.{nonce}.{body}`, and the signature is sent in the headers below.
+
+use crate::error::AssistantError;
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use log::{error, info};
+use serde::Deserialize;
+use sha3::{Digest, Keccak256};
+use std::path::Path;
+
+/// Header holding the signature: 65 bytes `r || s || v`, hex encoded with a `0x` prefix
+pub const SIGNATURE_HEADER: &str = "X-Gaianet-Signature";
+/// Header holding the address of the node signing the payload
+pub const ADDRESS_HEADER: &str = "X-Gaianet-Address";
+/// Header holding the Unix time of the signature, in seconds
+pub const TIMESTAMP_HEADER: &str = "X-Gaianet-Timestamp";
+/// Header holding the nonce, unique per request
+pub const NONCE_HEADER: &str = "X-Gaianet-Nonce";
+
+/// Key of the node, signing the payloads.
+pub struct NodeIdentity {
+    key: SigningKey,
+    address: String,
+}
+impl NodeIdentity {
+    /// Read `nodeid.json` and decrypt the keystore it refers to. The keystore path is
+    /// relative to the directory of `nodeid.json`.
+    pub fn load(path: &Path) -> Result<Self, AssistantError> {
+        let nodeid: NodeId = read_json(path)?;
+        let keystore_path = path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(&nodeid.keystore);
+        let keystore: Keystore = read_json(&keystore_path)?;
+
+        let identity = keystore
+            .decrypt(&nodeid.password)
+            .and_then(|secret| Self::from_secret(&secret))
+            .map_err(|e| invalid(&keystore_path, e))?;
+        if !identity.address.eq_ignore_ascii_case(&nodeid.address) {
+            return Err(invalid(
+                path,
+                format!(
+                    "address: {} differs from the address {} of the keystore",
+                    nodeid.address, identity.address
+                ),
+            ));
+        }
+        info!("Node address: {}", &identity.address);
+
+        Ok(identity)
+    }
+
+    /// Identity of the node holding the 32-byte secp256k1 secret key
+    pub fn from_secret(secret: &[u8]) -> Result<Self, String> {
+        let key = SigningKey::from_slice(secret).map_err(|_| "invalid secret key".to_string())?;
+        let address = address(key.verifying_key());
+        Ok(Self { key, address })
+    }
+
+    /// Address of the node, `0x`-prefixed and lowercase
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Sign the body of a request sent at `timestamp`, in Unix seconds, with a random nonce.
+    /// Fails if no random nonce can be generated, rather than signing with a predictable one
+    pub fn sign(&self, body: &[u8], timestamp: i64) -> Result<PayloadSignature, AssistantError> {
+        let mut nonce = [0u8; 16];
+        getrandom::getrandom(&mut nonce).map_err(|e| {
+            let err_msg = format!("Failed to generate a nonce: {}", e);
+            error!("{}", &err_msg);
+            AssistantError::Operation(err_msg)
+        })?;
+        Ok(self.sign_with(body, timestamp, hex::encode(nonce)))
+    }
+
+    fn sign_with(&self, body: &[u8], timestamp: i64, nonce: String) -> PayloadSignature {
+        let hash = message_hash(&signed_message(timestamp, &nonce, body));
+        // signing a 32-byte hash does not fail
+        let (signature, recovery_id) = self
+            .key
+            .sign_prehash_recoverable(&hash)
+            .expect("failed to sign the payload");
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        PayloadSignature {
+            address: self.address.clone(),
+            timestamp,
+            nonce,
+            signature: format!("0x{}", hex::encode(bytes)),
+        }
+    }
+}
+impl std::fmt::Debug for NodeIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeIdentity")
+            .field("address", &self.address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Signature of a payload, sent in the request headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadSignature {
+    /// Address of the node, `0x`-prefixed
+    pub address: String,
+    /// Unix time of the signature, in seconds
+    pub timestamp: i64,
+    /// Random hexadecimal string, unique per request
+    pub nonce: String,
+    /// 65 bytes `r || s || v`, hex encoded with a `0x` prefix, `v` being 27 or 28
+    pub signature: String,
+}
+impl PayloadSignature {
+    /// Headers of the request carrying the signature
+    pub fn headers(&self) -> [(&'static str, String); 4] {
+        [
+            (SIGNATURE_HEADER, self.signature.clone()),
+            (ADDRESS_HEADER, self.address.clone()),
+            (TIMESTAMP_HEADER, self.timestamp.to_string()),
+            (NONCE_HEADER, self.nonce.clone()),
+        ]
+    }
+
+    /// Read the signature from the request headers, `header` looking up a header by name
+    pub fn from_headers<F>(header: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let get = |name: &str| header(name).ok_or_else(|| format!("missing header {}", name));
+        let timestamp = get(TIMESTAMP_HEADER)?;
+        Ok(Self {
+            address: get(ADDRESS_HEADER)?,
+            timestamp: timestamp
+                .parse()
+                .map_err(|_| format!("invalid {}: {}", TIMESTAMP_HEADER, timestamp))?,
+            nonce: get(NONCE_HEADER)?,
+            signature: get(SIGNATURE_HEADER)?,
+        })
+    }
+
+    /// Check that `body` was signed by the key of `address`, with this timestamp and nonce.
+    ///
+    /// The receiver is expected to also reject stale timestamps and nonces seen before.
+    pub fn verify(&self, body: &[u8]) -> Result<(), String> {
+        let recovered = recover(
+            &signed_message(self.timestamp, &self.nonce, body),
+            &self.signature,
+        )?;
+        match recovered.eq_ignore_ascii_case(&self.address) {
+            true => Ok(()),
+            false => Err(format!("signed by {}, not by {}", recovered, self.address)),
+        }
+    }
+}
+
+/// Address of the key that signed `message` as an Ethereum personal message
+pub fn recover(message: &[u8], signature: &str) -> Result<String, String> {
+    let bytes = hex::decode(signature.trim_start_matches("0x"))
+        .map_err(|e| format!("invalid signature: {}", e))?;
+    if bytes.len() != 65 {
+        return Err(format!(
+            "invalid signature: expected 65 bytes, found {}",
+            bytes.len()
+        ));
+    }
+
+    let signature =
+        Signature::from_slice(&bytes[..64]).map_err(|e| format!("invalid signature: {}", e))?;
+    let recovery_id = RecoveryId::from_byte(bytes[64].wrapping_sub(27))
+        .ok_or_else(|| format!("invalid signature: recovery id {}", bytes[64]))?;
+    let key = VerifyingKey::recover_from_prehash(&message_hash(message), &signature, recovery_id)
+        .map_err(|e| format!("invalid signature: {}", e))?;
+
+    Ok(address(&key))
+}
+
+fn signed_message(timestamp: i64, nonce: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}.{}.", timestamp, nonce).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+// EIP-191 hash of a personal message
+fn message_hash(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()));
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+// last 20 bytes of the keccak256 of the uncompressed public key
+fn address(key: &VerifyingKey) -> String {
+    let point = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+    format!("0x{}", hex::encode(&hash[12..]))
+}
+
+// `nodeid.json`, written by `gaianet init`
+#[derive(Debug, Deserialize)]
+struct NodeId {
+    address: String,
+    keystore: String,
+    password: String,
+}
+
+// Ethereum keystore (v3)
+#[derive(Debug, Deserialize)]
+struct Keystore {
+    #[serde(alias = "Crypto")]
+    crypto: KeystoreCrypto,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeystoreCrypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    #[serde(with = "hex")]
+    ciphertext: Vec<u8>,
+    #[serde(flatten)]
+    kdf: Kdf,
+    #[serde(with = "hex")]
+    mac: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CipherParams {
+    #[serde(with = "hex")]
+    iv: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kdf", content = "kdfparams", rename_all = "lowercase")]
+enum Kdf {
+    Scrypt {
+        dklen: usize,
+        n: u64,
+        r: u32,
+        p: u32,
+        #[serde(with = "hex")]
+        salt: Vec<u8>,
+    },
+    Pbkdf2 {
+        dklen: usize,
+        c: u32,
+        prf: String,
+        #[serde(with = "hex")]
+        salt: Vec<u8>,
+    },
+}
+
+impl Keystore {
+    // secret key held by the keystore
+    fn decrypt(&self, password: &str) -> Result<Vec<u8>, String> {
+        let crypto = &self.crypto;
+        if crypto.cipher != "aes-128-ctr" {
+            return Err(format!("unsupported cipher {}", crypto.cipher));
+        }
+
+        let derived = crypto.kdf.derive(password.as_bytes())?;
+        if derived.len() < 32 {
+            return Err("dklen: must be at least 32".to_string());
+        }
+        let mac = Keccak256::new()
+            .chain_update(&derived[16..32])
+            .chain_update(&crypto.ciphertext)
+            .finalize();
+        if mac.as_slice() != crypto.mac.as_slice() {
+            return Err("wrong password or corrupted keystore".to_string());
+        }
+
+        use ctr::cipher::{KeyIvInit, StreamCipher};
+        let mut secret = crypto.ciphertext.clone();
+        ctr::Ctr128BE::<aes::Aes128>::new_from_slices(&derived[..16], &crypto.cipherparams.iv)
+            .map_err(|_| "cipherparams.iv: expected 16 bytes".to_string())?
+            .apply_keystream(&mut secret);
+        Ok(secret)
+    }
+}
+
+impl Kdf {
+    fn derive(&self, password: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Kdf::Scrypt {
+                dklen,
+                n,
+                r,
+                p,
+                salt,
+            } => {
+                if !n.is_power_of_two() || *n < 2 {
+                    return Err(format!("kdfparams.n: {} is not a power of 2", n));
+                }
+                let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p, *dklen)
+                    .map_err(|e| format!("kdfparams: {}", e))?;
+                let mut derived = vec![0u8; *dklen];
+                scrypt::scrypt(password, salt, &params, &mut derived)
+                    .map_err(|e| format!("kdfparams: {}", e))?;
+                Ok(derived)
+            }
+            Kdf::Pbkdf2 {
+                dklen,
+                c,
+                prf,
+                salt,
+            } => {
+                if prf != "hmac-sha256" {
+                    return Err(format!("unsupported prf {}", prf));
+                }
+                let mut derived = vec![0u8; *dklen];
+                pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password, salt, *c, &mut derived);
+                Ok(derived)
+            }
+        }
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, AssistantError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        let err_msg = format!("Failed to read {}: {}", path.display(), e);
+        error!("{}", &err_msg);
+        AssistantError::ConfigError(err_msg)
+    })?;
+    serde_json::from_str(&content).map_err(|e| invalid(path, e.to_string()))
+}
+
+fn invalid(path: &Path, e: String) -> AssistantError {
+    let err_msg = format!("Invalid {}: {}", path.display(), e);
+    error!("{}", &err_msg);
+    AssistantError::ConfigError(err_msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // key and signature of the web3.js documentation
+    const SECRET: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const ADDRESS: &str = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+    // SECRET encrypted with the password `gaianet`
+    fn keystore(kdf: &str) -> String {
+        let (kdf, ciphertext, mac) = match kdf {
+            "pbkdf2" => (
+                r#""kdf": "pbkdf2", "kdfparams": { "c": 1024, "dklen": 32, "prf": "hmac-sha256", "salt": "6c4e1d2f3a5b7c9d0e1f2a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f" }"#,
+                "1a8006bc5b494fde9208a3d47f2ecbe5250d8d2e06badacef9a3a03c701f6b8c",
+                "5f4f7f759f1203f943ba8abbbdefcf4750b555d0209c2790e38480bf47abbe5a",
+            ),
+            _ => (
+                r#""kdf": "scrypt", "kdfparams": { "n": 1024, "r": 8, "p": 1, "dklen": 32, "salt": "6c4e1d2f3a5b7c9d0e1f2a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f" }"#,
+                "c523f751c64f0c3084e0004c1c0bf229d4ed21297d964b590915ca0b14c77609",
+                "cae40202cd5820de9edab9b9da600bc27c52638e530812d3cf1b555ed8fed700",
+            ),
+        };
+        format!(
+            r#"{{ "version": 3, "address": "{}", "crypto": {{ "cipher": "aes-128-ctr", "cipherparams": {{ "iv": "83dbcc02d8ccb40e466191a123791e0e" }}, "ciphertext": "{}", {}, "mac": "{}" }} }}"#,
+            &ADDRESS[2..],
+            ciphertext,
+            kdf,
+            mac
+        )
+    }
+
+    #[test]
+    fn keystore_is_decrypted() {
+        for kdf in ["pbkdf2", "scrypt"] {
+            let keystore: Keystore = serde_json::from_str(&keystore(kdf)).unwrap();
+            assert_eq!(hex::encode(keystore.decrypt("gaianet").unwrap()), SECRET);
+            assert_eq!(
+                keystore.decrypt("wrong").unwrap_err(),
+                "wrong password or corrupted keystore"
+            );
+        }
+    }
+
+    #[test]
+    fn nodeid_gives_the_key() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("keystore.json"), keystore("pbkdf2")).unwrap();
+        let nodeid = dir.path().join("nodeid.json");
+        let write_nodeid = |address: &str| {
+            let content = format!(
+                r#"{{ "address": "{}", "keystore": "keystore.json", "password": "gaianet" }}"#,
+                address
+            );
+            std::fs::write(&nodeid, content).unwrap();
+        };
+
+        write_nodeid("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23");
+        assert_eq!(NodeIdentity::load(&nodeid).unwrap().address(), ADDRESS);
+
+        write_nodeid("0x0123456789abcdef0123456789abcdef01234567");
+        let err = NodeIdentity::load(&nodeid).unwrap_err().to_string();
+        assert!(err.contains("differs from the address"), "{}", err);
+    }
+
+    #[test]
+    fn personal_messages_are_recovered() {
+        let signature = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+        assert_eq!(recover(b"Some data", signature).unwrap(), ADDRESS);
+        assert_ne!(recover(b"Other data", signature).unwrap(), ADDRESS);
+    }
+
+    #[test]
+    fn signatures_are_verified() {
+        let identity = NodeIdentity::from_secret(&hex::decode(SECRET).unwrap()).unwrap();
+        let body = br#"{"health":true}"#;
+
+        let signature = identity.sign_with(body, 1721824000, "00ff".to_string());
+        assert_eq!(signature.address, ADDRESS);
+        assert_eq!(signature.verify(body), Ok(()));
+
+        // the body, timestamp and nonce are all signed
+        assert!(signature.verify(br#"{"health":false}"#).is_err());
+        let mut replayed = signature.clone();
+        replayed.timestamp += 60;
+        assert!(replayed.verify(body).is_err());
+
+        let headers = signature.headers();
+        let parsed = PayloadSignature::from_headers(|name| {
+            headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        })
+        .unwrap();
+        assert_eq!(parsed, signature);
+    }
+
+    #[test]
+    fn each_signature_has_its_own_nonce() {
+        let identity = NodeIdentity::from_secret(&hex::decode(SECRET).unwrap()).unwrap();
+        let body = br#"{"health":true}"#;
+
+        let first = identity.sign(body, 1722513600).unwrap();
+        let second = identity.sign(body, 1722513600).unwrap();
+        assert_eq!(first.timestamp, 1722513600);
+        assert_eq!(first.verify(body), Ok(()));
+        assert_eq!(second.verify(body), Ok(()));
+        assert_eq!(first.nonce.len(), 32);
+        assert_ne!(first.nonce, second.nonce);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{
+    assistant::InfoExtras, cloudevents::EventType, delivery::Delivery, endpoint::ApiServerEndpoint,
+    error::AssistantError, gguf::GgufMetadata, health::is_auth_error, Subscribers,
+};
+use log::{debug, error, info, warn};
+use serde_json::Value;
+
+// Retrieve server information from the LlamaEdge API Server
+pub(crate) async fn retrieve_server_info(
+    endpoint: &ApiServerEndpoint,
+    extras: &InfoExtras,
+    client: &reqwest::Client,
+    api_key: Option<&str>,
+) -> Result<Value, AssistantError> {
+    let system_prompt = &extras.system_prompt;
+    let rag_prompt = &extras.rag_prompt;
+    let sha256_chat_model = &extras.sha256_chat_model;
+    let sha256_embedding_model = &extras.sha256_embedding_model;
+
+    // send a request to the LlamaEdge API Server to get the server information
+    let url = endpoint.url("/v1/info");
+
+    info!("Retrieving server information from: {}", &url);
+
+    let mut request = client.get(&url);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let response = match request.send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            let err_msg = format!("Failed to send a request: {}", e);
+            error!("{}", &err_msg);
+            return Err(AssistantError::Operation(err_msg));
+        }
+    };
+
+    if is_auth_error(response.status()) {
+        let err_msg = format!(
+            "The API server rejected the API key. Status: {}",
+            response.status()
+        );
+        error!("{}", &err_msg);
+        return Err(AssistantError::Unauthorized(err_msg));
+    }
+    if !response.status().is_success() {
+        let err_msg = format!(
+            "Failed to get server info from API Server. Status: {}",
+            response.status()
+        );
+        error!("{}", &err_msg);
+        return Err(AssistantError::Operation(err_msg));
+    }
+
+    // parse the response
+    let mut server_info = match response.json::<serde_json::Value>().await {
+        Ok(json) => json,
+        Err(e) => {
+            let err_msg = format!("Failed to parse the response: {}", e);
+            error!("{}", &err_msg);
+            return Err(AssistantError::Operation(err_msg));
+        }
+    };
+    debug!("raw server info: {}", server_info);
+
+    // get the server type
+    let server_type = match server_info["api_server"]["type"].as_str() {
+        Some(server_type) => server_type.to_string(),
+        None => {
+            let err_msg = "Failed to get the server type.".to_string();
+            error!("{}", &err_msg);
+            return Err(AssistantError::Operation(err_msg));
+        }
+    };
+    info!("server type: {}", server_type);
+
+    // add the rag prompt to the server information if the server type is `rag`
+    if server_type == "rag" {
+        if let Some(map) = server_info.as_object_mut() {
+            info!("insert rag prompt to server info: {}", system_prompt);
+            map.insert(
+                "rag_prompt".to_string(),
+                serde_json::Value::String(rag_prompt.to_string()),
+            );
+        }
+    }
+
+    // add the system prompt to the server information
+    if let Some(extra) = server_info["extras"].as_object_mut() {
+        info!("insert system prompt to server info: {}", system_prompt);
+
+        extra.insert(
+            "system_prompt".to_string(),
+            serde_json::Value::String(system_prompt.to_string()),
+        );
+    }
+
+    // add sha256 of chat model to the server information
+    if let Some(map) = server_info["chat_model"].as_object_mut() {
+        if !sha256_chat_model.is_empty() {
+            map.insert(
+                "sha256".to_string(),
+                serde_json::Value::String(sha256_chat_model.to_string()),
+            );
+        }
+    }
+
+    // add sha256 of embedding model to the server information
+    if let Some(map) = server_info["embedding_model"].as_object_mut() {
+        if !sha256_embedding_model.is_empty() {
+            map.insert(
+                "sha256".to_string(),
+                serde_json::Value::String(sha256_embedding_model.to_string()),
+            );
+        }
+    }
+
+    // add the metadata of the model files to the server information
+    add_gguf(&mut server_info["chat_model"], &extras.gguf_chat_model);
+    add_gguf(
+        &mut server_info["embedding_model"],
+        &extras.gguf_embedding_model,
+    );
+
+    // get system info
+    match system_info_lite::get_system_info() {
+        Ok(system_info) => {
+            info!("hardware info: {:?}", system_info);
+            let sys_info = serde_json::to_value(system_info).unwrap();
+
+            // add hardware info to the server information
+            if let Some(map) = server_info.as_object_mut() {
+                map.insert("hardware".to_string(), sys_info);
+            }
+        }
+        Err(e) => {
+            error!("Failed to get system info: {}", e);
+        }
+    }
+
+    info!("server info: {}", server_info);
+
+    Ok(server_info)
+}
+
+// Add the GGUF metadata of the model under `gguf`, if the model is reported
+fn add_gguf(model: &mut Value, gguf: &Option<GgufMetadata>) {
+    if let (Some(map), Some(gguf)) = (model.as_object_mut(), gguf) {
+        match serde_json::to_value(gguf) {
+            Ok(gguf) => {
+                map.insert("gguf".to_string(), gguf);
+            }
+            Err(e) => {
+                error!("Failed to serialize the GGUF metadata: {}", e);
+            }
+        }
+    }
+}
+
+// Push server information to all subscribers
+pub(crate) async fn push_server_info(
+    subscribers: Subscribers,
+    server_info: &Value,
+    retries: u32,
+    delivery: &Delivery,
+    event_type: EventType,
+    source: Option<&str>,
+) -> Result<(), AssistantError> {
+    let subs = subscribers.read().await;
+    match subs.is_empty() {
+        true => {
+            let err_msg = "No subscribers found.".to_string();
+
+            error!("{}", &err_msg);
+
+            Err(AssistantError::Operation(err_msg))
+        }
+        false => {
+            let server_info_str = match serde_json::to_string(server_info) {
+                Ok(info) => info,
+                Err(e) => {
+                    let err_msg = format!("Failed to serialize the server information. {}", e);
+                    error!("{}", &err_msg);
+                    return Err(AssistantError::Operation(err_msg));
+                }
+            };
+
+            // one event, whatever the subscribers and the retries
+            let message = delivery.message(event_type, source, &server_info_str);
+            for url in subs.iter() {
+                let mut retry = 0;
+
+                // retry if the request fails to send
+                loop {
+                    info!("tries ({}) to send server info to {}", retry, &url);
+
+                    // send request using reqwest
+                    // a request that cannot be signed is not sent
+                    let response = match delivery.post(url, &message)?.send().await {
+                        Ok(resp) => resp,
+                        Err(e) => {
+                            retry += 1;
+                            if retry >= retries {
+                                let err_msg = format!(
+                                    "Failed to send server information to {}: {}",
+                                    &url, e,
+                                );
+                                error!("{}", &err_msg);
+                                return Err(AssistantError::Operation(err_msg));
+                            } else {
+                                let err_msg = format!(
+                                    "Failed to send server information to {}: {}. Retrying ({})...",
+                                    &url, e, retry
+                                );
+                                warn!("{}", &err_msg);
+                                continue;
+                            }
+                        }
+                    };
+
+                    // check if the request was successful
+                    if response.status().is_success() {
+                        info!("Server info sent to {} successfully!", &url);
+                        break;
+                    } else {
+                        retry += 1;
+                        if retry >= retries {
+                            error!("Failed to get server information from {}.", &url);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    assistant::{Assistant, Event},
+    error::AssistantError,
+    health::Issue,
+    models::{Fingerprint, ModelFile, ModelKind},
+    schedule::Task,
+};
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::{io, path::Path};
+
+/// Expected sha256 of the model files, read from `models.lock`:
+///
+/// ```toml
+/// [[model]]
+/// url = "https://huggingface.co/gaianet/Llama-3-8B-Instruct-GGUF/resolve/main/Meta-Llama-3-8B-Instruct-Q5_K_M.gguf"
+/// sha256 = "..."
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ModelsLock {
+    #[serde(default, rename = "model")]
+    models: Vec<LockedModel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LockedModel {
+    url: String,
+    sha256: String,
+}
+
+impl ModelsLock {
+    /// Read the lock file. A missing lock file is empty
+    pub(crate) fn load(path: &Path) -> Result<Self, AssistantError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                let err_msg = format!("Failed to read {}: {}", path.display(), e);
+                error!("{}", &err_msg);
+                return Err(AssistantError::ConfigError(err_msg));
+            }
+        };
+
+        toml::from_str(&content).map_err(|e| {
+            let err_msg = format!("Failed to parse {}: {}", path.display(), e);
+            error!("{}", &err_msg);
+            AssistantError::ConfigError(err_msg)
+        })
+    }
+
+    /// Expected sha256 of the model downloaded from `url`
+    pub(crate) fn sha256(&self, url: &str) -> Option<&str> {
+        self.models
+            .iter()
+            .find(|model| model.url == url)
+            .map(|model| model.sha256.as_str())
+    }
+}
+
+impl Assistant {
+    // Compare the hash of the model with the expected one, reporting the server unhealthy
+    // on a mismatch
+    pub(crate) async fn check_integrity(&self, model: &ModelFile, sha256: &str) {
+        let Some(expected) = &model.expected_sha256 else {
+            self.clear_integrity(model.kind).await;
+            return;
+        };
+
+        self.issues
+            .write()
+            .await
+            .remove(&Issue::ModelMissing(model.kind));
+        let issue = Issue::ModelHashMismatch(model.kind);
+        if expected.eq_ignore_ascii_case(sha256) {
+            if self.issues.write().await.remove(&issue) {
+                info!("The {} model matches its expected sha256 again", model.kind);
+            } else {
+                info!("The {} model matches its expected sha256", model.kind);
+            }
+            return;
+        }
+
+        error!(
+            "model_hash_mismatch: {} model {}: expected {}, found {}",
+            model.kind,
+            model.path.display(),
+            expected,
+            sha256
+        );
+        if self.issues.write().await.insert(issue) {
+            let _ = self.events.send(Event::ModelHashMismatch {
+                kind: model.kind,
+                path: model.path.clone(),
+                expected: expected.clone(),
+                actual: sha256.to_string(),
+            });
+        }
+    }
+
+    // Report the model as missing if it has an expected sha256, its file being gone or
+    // unreadable
+    pub(crate) async fn check_missing(&self, model: &ModelFile) {
+        if model.expected_sha256.is_none() {
+            self.clear_integrity(model.kind).await;
+            return;
+        }
+
+        error!(
+            "model_missing: {} model {} is missing or unreadable",
+            model.kind,
+            model.path.display()
+        );
+        let mut issues = self.issues.write().await;
+        issues.remove(&Issue::ModelHashMismatch(model.kind));
+        issues.insert(Issue::ModelMissing(model.kind));
+    }
+
+    // Forget the result of the verification of the model
+    pub(crate) async fn clear_integrity(&self, kind: ModelKind) {
+        let mut issues = self.issues.write().await;
+        issues.remove(&Issue::ModelHashMismatch(kind));
+        issues.remove(&Issue::ModelMissing(kind));
+    }
+
+    // Verify the known hashes again, after the models or their expected hashes changed
+    pub(crate) async fn check_models(&self) {
+        let models = self.models.read().await.clone();
+        for kind in [ModelKind::Chat, ModelKind::Embedding] {
+            if !models.iter().any(|model| model.kind == kind) {
+                self.clear_integrity(kind).await;
+            }
+        }
+
+        for model in models.iter() {
+            let sha256 = self.extras.read().await.sha256(model.kind).to_string();
+            if !sha256.is_empty() {
+                self.check_integrity(model, &sha256).await;
+            }
+        }
+    }
+
+    // Hash the models with an expected sha256 from scratch on the model re-hash schedule, so
+    // that changes keeping the size, the modification time and the inode are caught
+    pub(crate) async fn verify_models(&self) {
+        let ticker = self.ticker(Task::ModelRehash);
+        loop {
+            ticker.wait().await;
+
+            let models = self.models.read().await.clone();
+            for model in models
+                .iter()
+                .filter(|model| model.expected_sha256.is_some())
+            {
+                info!("Verify the {} model", model.kind);
+                let fingerprint = match Fingerprint::of(&model.path) {
+                    Ok(fingerprint) => fingerprint,
+                    Err(e) => {
+                        warn!(
+                            "Skip verifying the {} model {}: {}",
+                            model.kind,
+                            model.path.display(),
+                            e
+                        );
+                        self.check_missing(model).await;
+                        continue;
+                    }
+                };
+
+                if let Some(sha256) = self.compute_sha256(model, fingerprint).await {
+                    self.update_sha256(model, Some(sha256)).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_file_lists_expected_hashes() {
+        let lock: ModelsLock = toml::from_str(
+            r#"
+            [[model]]
+            url = "https://example.com/chat.gguf"
+            sha256 = "abc"
+
+            [[model]]
+            url = "https://example.com/embedding.gguf"
+            sha256 = "def"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            lock.sha256("https://example.com/embedding.gguf"),
+            Some("def")
+        );
+        assert_eq!(lock.sha256("https://example.com/other.gguf"), None);
+    }
+
+    #[test]
+    fn missing_lock_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            ModelsLock::load(&dir.path().join("models.lock")).unwrap(),
+            ModelsLock::default()
+        );
+    }
+
+    #[test]
+    fn invalid_lock_file_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("models.lock");
+        std::fs::write(&path, "[[model]]\nurl = \"x\"\n").unwrap();
+
+        let err = ModelsLock::load(&path).unwrap_err();
+
+        assert!(err.to_string().contains("sha256"));
+    }
+}
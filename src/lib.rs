@@ -0,0 +1,69 @@
+//! An assistant for LlamaEdge API Server.
+//!
+//! The [`Assistant`] retrieves the information of a LlamaEdge API server and pushes it to
+//! subscribers, then keeps checking the health of the server, from its log file and from
+//! synthetic requests, and pushes it periodically.
+
+mod assistant;
+pub mod clock;
+mod cloudevents;
+pub mod config;
+mod consistency;
+mod delivery;
+pub mod doctor;
+pub mod dry_run;
+pub mod endpoint;
+pub mod error;
+pub mod gaianet;
+mod gguf;
+pub mod health;
+pub mod http;
+mod hub;
+pub mod identity;
+mod info;
+mod integrity;
+mod models;
+mod notification;
+pub mod probe;
+mod reload;
+pub mod schedule;
+mod shutdown;
+pub mod webhook;
+
+pub use assistant::{Assistant, AssistantBuilder, ConfigLoader, Event};
+pub use endpoint::ApiServerEndpoint;
+pub use models::ModelKind;
+pub use shutdown::ShutdownReason;
+
+use config::ProbeConfig;
+use health::{ApiServerStats, Issue};
+use schedule::Schedules;
+use serde_json::Value;
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::Arc,
+};
+use tokio::sync::RwLock;
+
+pub(crate) type Subscribers = Arc<RwLock<HashSet<String>>>;
+pub(crate) type ServerLogFile = Arc<RwLock<String>>;
+pub(crate) type SharedSchedules = Arc<RwLock<Schedules>>;
+pub(crate) type ProbeSettings = Arc<RwLock<ProbeConfig>>;
+// `None` until the server information is retrieved
+pub(crate) type ServerInfo = Arc<RwLock<Option<Value>>>;
+// `None` until the first health check completes
+pub(crate) type ServerHealth = Arc<RwLock<Option<bool>>>;
+// reported along with the server health
+pub(crate) type Issues = Arc<RwLock<BTreeSet<Issue>>>;
+pub(crate) type HealthStats = Arc<RwLock<ApiServerStats>>;
+// client of the requests to the API server, rebuilt when the `[http]` settings are reloaded
+pub(crate) type ApiClient = Arc<RwLock<reqwest::Client>>;
+// bearer token of the requests to the API server, read again on reload
+pub(crate) type ApiKey = Arc<RwLock<Option<String>>>;
+
+/// Default socket address of LlamaEdge API Server instance
+pub const DEFAULT_SERVER_SOCKET_ADDRESS: &str = "0.0.0.0:8080";
+/// Default interval in seconds for checking the health and sending notifications
+pub const DEFAULT_INTERVAL: u64 = 10;
+/// Default seconds without logged responses before the API server is pinged
+pub const MAX_TIME_SPAN_IN_SECONDS: i64 = 30;
@@ -0,0 +1,485 @@
+use crate::{
+    assistant::{Assistant, Event},
+    cloudevents::EventType,
+    error::AssistantError,
+    gaianet::GaianetConfig,
+    gguf::load_metadata,
+    integrity::ModelsLock,
+    reload::WATCH_INTERVAL,
+    shutdown::Shutdown,
+};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+// size of the chunks read while hashing
+const CHUNK_SIZE: usize = 1024 * 1024;
+// progress is logged every time this percentage of the model is hashed
+const PROGRESS_STEP: u64 = 10;
+
+/// Kind of a model served by the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ModelKind {
+    Chat,
+    Embedding,
+}
+impl fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelKind::Chat => write!(f, "chat"),
+            ModelKind::Embedding => write!(f, "embedding"),
+        }
+    }
+}
+
+/// Model file in the gaianet directory.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ModelFile {
+    pub(crate) kind: ModelKind,
+    // where the model is downloaded from, in `config.json`
+    pub(crate) url: String,
+    pub(crate) path: PathBuf,
+    // from `models.lock` or `config.json`, if any
+    pub(crate) expected_sha256: Option<String>,
+}
+impl ModelFile {
+    // Model downloaded from `url` into the gaianet directory. None if not configured
+    pub(crate) fn from_url(gaianet_dir: &Path, url: &str, kind: ModelKind) -> Option<Self> {
+        let name = url.split('/').next_back().filter(|name| !name.is_empty())?;
+
+        Some(Self {
+            kind,
+            url: url.to_string(),
+            path: gaianet_dir.join(name),
+            expected_sha256: None,
+        })
+    }
+}
+
+// Models configured in `config.json`, with the expected sha256 from `models.lock` or else
+// from `config.json`
+pub(crate) fn node_models(
+    gaianet_dir: &Path,
+    config: &GaianetConfig,
+    lock: &ModelsLock,
+) -> Vec<ModelFile> {
+    [
+        (&config.chat_url, &config.chat_sha256, ModelKind::Chat),
+        (
+            &config.embedding_url,
+            &config.embedding_sha256,
+            ModelKind::Embedding,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(url, sha256, kind)| {
+        let mut model = ModelFile::from_url(gaianet_dir, url, kind)?;
+        model.expected_sha256 = lock
+            .sha256(url)
+            .or(Some(sha256.as_str()).filter(|sha256| !sha256.is_empty()))
+            .map(str::to_lowercase);
+        Some(model)
+    })
+    .collect()
+}
+
+/// Identity of a file version: a hash is reused while none of these change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Fingerprint {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    inode: u64,
+}
+impl Fingerprint {
+    pub(crate) fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&metadata);
+        #[cfg(not(unix))]
+        let inode = 0;
+
+        Ok(Self {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            inode,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedHash {
+    #[serde(flatten)]
+    fingerprint: Fingerprint,
+    sha256: String,
+}
+
+/// Hashes of the model files, stored in a sidecar file and keyed by path.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HashCache {
+    models: BTreeMap<PathBuf, CachedHash>,
+}
+impl HashCache {
+    /// Read the cache file. A missing or invalid cache is empty
+    pub(crate) fn load(path: &Path) -> Self {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Failed to read the hash cache {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignore the invalid hash cache {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    /// Write the cache file, replacing it atomically
+    pub(crate) fn save(&self, path: &Path) -> Result<(), AssistantError> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| {
+            AssistantError::Operation(format!("Failed to serialize the hash cache: {}", e))
+        })?;
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content)
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| {
+                let err_msg = format!("Failed to write the hash cache {}: {}", path.display(), e);
+                error!("{}", &err_msg);
+                AssistantError::Operation(err_msg)
+            })
+    }
+
+    /// Cached hash of the file, if the file did not change since it was hashed
+    pub(crate) fn get(&self, path: &Path, fingerprint: &Fingerprint) -> Option<&str> {
+        self.models
+            .get(path)
+            .filter(|cached| cached.fingerprint == *fingerprint)
+            .map(|cached| cached.sha256.as_str())
+    }
+
+    pub(crate) fn insert(&mut self, path: PathBuf, fingerprint: Fingerprint, sha256: String) {
+        self.models.insert(
+            path,
+            CachedHash {
+                fingerprint,
+                sha256,
+            },
+        );
+    }
+}
+
+// Compute sha256 of the model, logging the progress. Blocking, so run it with
+// `spawn_blocking`. Returns None if the shutdown is requested meanwhile.
+pub(crate) fn hash_model(
+    model: &ModelFile,
+    shutdown: &Shutdown,
+) -> Result<Option<String>, AssistantError> {
+    let read_error = |e: io::Error| {
+        let err_msg = format!(
+            "Failed to read the {} model {}: {}",
+            model.kind,
+            model.path.display(),
+            e
+        );
+        error!("{}", &err_msg);
+        AssistantError::Operation(err_msg)
+    };
+
+    let mut file = File::open(&model.path).map_err(read_error)?;
+    let size = file.metadata().map_err(read_error)?.len();
+    info!(
+        "Computing sha256 of the {} model {} ({} bytes)",
+        model.kind,
+        model.path.display(),
+        size
+    );
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut hashed = 0u64;
+    let mut logged = 0;
+    loop {
+        if shutdown.is_requested() {
+            info!("Stop computing sha256 of the {} model", model.kind);
+            return Ok(None);
+        }
+
+        let read = file.read(&mut buffer).map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        hashed += read as u64;
+
+        let percent = hashed * 100 / size.max(1);
+        if percent >= logged + PROGRESS_STEP {
+            logged = percent - percent % PROGRESS_STEP;
+            info!("Hashing the {} model: {}%", model.kind, logged);
+        }
+    }
+
+    let sha256 = format!("{:x}", hasher.finalize());
+    info!("sha256 of {} model: {}", model.kind, &sha256);
+
+    Ok(Some(sha256))
+}
+
+impl Assistant {
+    // Hash the models without a cached hash in the background.
+    // Returns whether a hash was added to the server information
+    pub(crate) async fn hash_models(&self) -> bool {
+        let models = self.models.read().await.clone();
+        let mut hashed = false;
+        for model in models.iter() {
+            // verify the cached hashes
+            let known = self.extras.read().await.sha256(model.kind).to_string();
+            if !known.is_empty() {
+                self.check_integrity(model, &known).await;
+                continue;
+            }
+            let fingerprint = match Fingerprint::of(&model.path) {
+                Ok(fingerprint) => fingerprint,
+                Err(e) => {
+                    warn!(
+                        "Skip hashing the {} model {}: {}",
+                        model.kind,
+                        model.path.display(),
+                        e
+                    );
+                    self.check_missing(model).await;
+                    continue;
+                }
+            };
+
+            if self.shutdown.is_requested() {
+                return false;
+            }
+            if let Some(sha256) = self.model_sha256(model, fingerprint).await {
+                self.check_integrity(model, &sha256).await;
+                self.extras.write().await.set_sha256(model.kind, sha256);
+                hashed = true;
+            }
+        }
+
+        hashed
+    }
+
+    // Hash a model again when its file changes, once the file is no longer being written
+    pub(crate) async fn watch_models(&self) {
+        // fingerprints of the files when they were last hashed, and when last polled
+        let mut hashed = HashMap::new();
+        for model in self.models.read().await.iter() {
+            hashed.insert(model.path.clone(), Fingerprint::of(&model.path).ok());
+        }
+        let mut polled = hashed.clone();
+
+        loop {
+            self.clock.sleep(WATCH_INTERVAL).await;
+
+            let models = self.models.read().await.clone();
+            hashed.retain(|path, _| models.iter().any(|model| &model.path == path));
+            polled.retain(|path, _| models.iter().any(|model| &model.path == path));
+            for model in models.iter() {
+                let current = Fingerprint::of(&model.path).ok();
+                // the models configured on reload are hashed by the reload
+                if !hashed.contains_key(&model.path) {
+                    hashed.insert(model.path.clone(), current);
+                    polled.insert(model.path.clone(), current);
+                    continue;
+                }
+                let previous = polled.insert(model.path.clone(), current);
+                if hashed.get(&model.path) == Some(&current) || previous != Some(current) {
+                    continue;
+                }
+
+                hashed.insert(model.path.clone(), current);
+                self.model_changed(model, current).await;
+            }
+        }
+    }
+
+    // Hash the changed model, then apply the new hash
+    pub(crate) async fn model_changed(&self, model: &ModelFile, fingerprint: Option<Fingerprint>) {
+        info!("The {} model changed: {}", model.kind, model.path.display());
+
+        let new_sha256 = match fingerprint {
+            Some(fingerprint) => match self.model_sha256(model, fingerprint).await {
+                Some(sha256) => Some(sha256),
+                None => return,
+            },
+            None => None,
+        };
+
+        self.update_sha256(model, new_sha256).await;
+    }
+
+    // Verify the new hash of the model, and if it differs from the previous one, push the
+    // server information and emit `ModelChanged`
+    pub(crate) async fn update_sha256(&self, model: &ModelFile, new_sha256: Option<String>) {
+        match &new_sha256 {
+            Some(sha256) => self.check_integrity(model, sha256).await,
+            None => self.check_missing(model).await,
+        }
+
+        let old_sha256 = {
+            let mut extras = self.extras.write().await;
+            let old_sha256 = extras.sha256(model.kind).to_string();
+            extras.set_sha256(model.kind, new_sha256.clone().unwrap_or_default());
+            Some(old_sha256).filter(|sha256| !sha256.is_empty())
+        };
+        if old_sha256 == new_sha256 {
+            info!("The content of the {} model is unchanged", model.kind);
+            return;
+        }
+
+        let gguf = match new_sha256 {
+            Some(_) => load_metadata(&model.path).await,
+            None => None,
+        };
+        self.extras.write().await.set_gguf(model.kind, gguf);
+
+        info!(
+            "model_changed: {} model {}: {} -> {}",
+            model.kind,
+            model.path.display(),
+            old_sha256.as_deref().unwrap_or("none"),
+            new_sha256.as_deref().unwrap_or("none")
+        );
+        let _ = self.events.send(Event::ModelChanged {
+            kind: model.kind,
+            path: model.path.clone(),
+            old_sha256,
+            new_sha256,
+        });
+
+        let _ = self.refresh_info_as(EventType::ModelChanged).await;
+    }
+
+    // Cached sha256 of the model, or computed in a blocking task and cached. None if the
+    // model cannot be read or the shutdown is requested
+    async fn model_sha256(&self, model: &ModelFile, fingerprint: Fingerprint) -> Option<String> {
+        let cache_file = self.hash_cache.as_deref();
+        if let Some(sha256) = cache_file
+            .map(HashCache::load)
+            .and_then(|cache| cache.get(&model.path, &fingerprint).map(str::to_string))
+        {
+            info!("sha256 of {} model (cached): {}", model.kind, &sha256);
+            return Some(sha256);
+        }
+
+        self.compute_sha256(model, fingerprint).await
+    }
+
+    // Compute sha256 of the model in a blocking task, and cache it
+    pub(crate) async fn compute_sha256(
+        &self,
+        model: &ModelFile,
+        fingerprint: Fingerprint,
+    ) -> Option<String> {
+        let (job, shutdown) = (model.clone(), self.shutdown.clone());
+        let sha256 = match tokio::task::spawn_blocking(move || hash_model(&job, &shutdown)).await {
+            Ok(Ok(Some(sha256))) => sha256,
+            Ok(Ok(None)) | Ok(Err(_)) => return None,
+            Err(e) => {
+                error!("Failed to hash the {} model: {}", model.kind, e);
+                return None;
+            }
+        };
+
+        if let Some(cache_file) = self.hash_cache.as_deref() {
+            let mut cache = HashCache::load(cache_file);
+            cache.insert(model.path.clone(), fingerprint, sha256.clone());
+            let _ = cache.save(cache_file);
+        }
+
+        Some(sha256)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+
+    #[test]
+    fn cached_hash_is_invalidated_by_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let model = dir.path().join("model.gguf");
+        fs::write(&model, b"weights").unwrap();
+        let fingerprint = Fingerprint::of(&model).unwrap();
+
+        let cache_file = dir.path().join("hashes.json");
+        let mut cache = HashCache::default();
+        cache.insert(model.clone(), fingerprint, "abc".to_string());
+        cache.save(&cache_file).unwrap();
+
+        let cache = HashCache::load(&cache_file);
+        assert_eq!(cache.get(&model, &fingerprint), Some("abc"));
+
+        fs::write(&model, b"other weights").unwrap();
+        let changed = Fingerprint::of(&model).unwrap();
+        assert_eq!(cache.get(&model, &changed), None);
+    }
+
+    #[test]
+    fn invalid_cache_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_file = dir.path().join("hashes.json");
+        fs::write(&cache_file, "not json").unwrap();
+
+        assert_eq!(HashCache::load(&cache_file), HashCache::default());
+    }
+
+    #[test]
+    fn hash_matches_sha256_of_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        fs::write(&path, vec![7u8; 3 * CHUNK_SIZE + 5]).unwrap();
+        let model = ModelFile {
+            kind: ModelKind::Chat,
+            url: "https://example.com/model.gguf".to_string(),
+            path: path.clone(),
+            expected_sha256: None,
+        };
+
+        let sha256 = hash_model(&model, &Shutdown::new()).unwrap();
+
+        assert_eq!(sha256, Some(sha256::try_digest(path.as_path()).unwrap()));
+    }
+
+    #[test]
+    fn hashing_stops_on_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        fs::write(&path, b"weights").unwrap();
+        let shutdown = Shutdown::new();
+        shutdown.request(ShutdownReason::AssistantShutdown);
+
+        let model = ModelFile {
+            kind: ModelKind::Embedding,
+            url: "https://example.com/model.gguf".to_string(),
+            path,
+            expected_sha256: None,
+        };
+
+        assert_eq!(hash_model(&model, &shutdown).unwrap(), None);
+    }
+}
@@ -0,0 +1,466 @@
+use crate::{
+    assistant::{Event, EventSender},
+    clock::{SharedClock, Sleep, Ticker},
+    cloudevents::EventType,
+    config::{HealthSchema, NotificationMode},
+    delivery::Delivery,
+    dry_run::{DryRun, Payload},
+    gaianet::NodeConfig,
+    health::{Failure, HealthStatus, Issue},
+    shutdown::{Shutdown, ShutdownReason, SHUTDOWN_TIMEOUT},
+    HealthStats, Issues, ServerHealth, Subscribers,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{broadcast::error::RecvError, RwLock};
+
+// version of the health payloads sent with `notifications.schema = "v2"`, the legacy
+// payloads carry none
+pub(crate) const HEALTH_SCHEMA_VERSION: u32 = 2;
+
+// Legacy health payload, understood by all hubs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Notification {
+    health: bool,
+    // why the node is going down, in the final notification only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+impl Notification {
+    // a responding server is still unhealthy with issues, but not with minor ones
+    fn new(health: bool, issues: &BTreeSet<Issue>) -> Self {
+        Self {
+            health: status(health, issues) != HealthStatus::Unhealthy,
+            reason: None,
+        }
+    }
+}
+
+// Versioned health payload
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct HealthPayload {
+    schema_version: u32,
+    // `None` without a gaianet directory
+    device_id: Option<String>,
+    assistant_version: &'static str,
+    timestamp: String,
+    sequence: u64,
+    status: HealthStatus,
+    // the issues of the server, or why it is going down
+    reasons: Vec<String>,
+    since_change: u64,
+    last_successful_probe: Option<String>,
+    requests: RequestStats,
+    uptime: Uptime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct RequestStats {
+    total: u64,
+    failed: u64,
+}
+
+// in seconds, `api_server` is `None` while the API server does not respond
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Uptime {
+    assistant: u64,
+    api_server: Option<u64>,
+}
+
+// Health last pushed, compared to tell changes
+#[derive(Debug, Default)]
+struct Pushed {
+    sequence: u64,
+    last: Option<(bool, BTreeSet<Issue>)>,
+    changed_at: Option<DateTime<Utc>>,
+}
+
+// Pushes the server health to its subscribers
+#[derive(Clone)]
+pub(crate) struct HealthNotifier {
+    pub(crate) subscribers: Subscribers,
+    pub(crate) server_health: ServerHealth,
+    pub(crate) issues: Issues,
+    pub(crate) stats: HealthStats,
+    pub(crate) node: Arc<RwLock<Option<NodeConfig>>>,
+    pub(crate) mode: NotificationMode,
+    pub(crate) schema: HealthSchema,
+    pub(crate) started_at: DateTime<Utc>,
+    pub(crate) clock: SharedClock,
+    pub(crate) shutdown: Shutdown,
+    pub(crate) dry_run: Option<Arc<DryRun>>,
+    pub(crate) delivery: Arc<Delivery>,
+    pushed: Arc<Mutex<Pushed>>,
+}
+impl HealthNotifier {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        subscribers: Subscribers,
+        server_health: ServerHealth,
+        issues: Issues,
+        stats: HealthStats,
+        node: Arc<RwLock<Option<NodeConfig>>>,
+        mode: NotificationMode,
+        schema: HealthSchema,
+        started_at: DateTime<Utc>,
+        clock: SharedClock,
+        shutdown: Shutdown,
+        dry_run: Option<Arc<DryRun>>,
+        delivery: Arc<Delivery>,
+    ) -> Self {
+        Self {
+            subscribers,
+            server_health,
+            issues,
+            stats,
+            node,
+            mode,
+            schema,
+            started_at,
+            clock,
+            shutdown,
+            dry_run,
+            delivery,
+            pushed: Default::default(),
+        }
+    }
+
+    // Periodically send notifications to all subscribers
+    pub(crate) async fn periodic(&self, ticker: Ticker) {
+        loop {
+            self.push(true).await;
+
+            // a notification being sent is not interrupted by the shutdown
+            tokio::select! {
+                _ = ticker.wait() => {}
+                _ = self.shutdown.requested() => return,
+            }
+        }
+    }
+
+    // Send a notification as soon as the health changes, and a heartbeat when it has not
+    // changed for a while. Changes of the issues without an event are caught on the next
+    // log scan.
+    pub(crate) async fn on_change(
+        &self,
+        log_scan: Ticker,
+        heartbeat: Ticker,
+        events: &EventSender,
+    ) {
+        let mut events = events.subscribe();
+        let mut sent_at = self.clock.now();
+        let mut next_heartbeat = None;
+        loop {
+            let now = self.clock.now();
+            if self
+                .push(next_heartbeat.is_some_and(|next| now >= next))
+                .await
+            {
+                sent_at = now;
+                next_heartbeat = heartbeat.next(now).await;
+            }
+
+            let now = self.clock.now();
+            let sleep: Sleep = match next_heartbeat {
+                Some(next) => self
+                    .clock
+                    .sleep((next - now).to_std().unwrap_or(Duration::ZERO)),
+                None => Box::pin(std::future::pending()),
+            };
+            tokio::select! {
+                _ = sleep => {}
+                _ = log_scan.wait() => {}
+                event = events.recv() => match event {
+                    // the heartbeat schedule may have changed
+                    Ok(Event::Reloaded) => next_heartbeat = heartbeat.next(sent_at).await,
+                    Err(RecvError::Closed) => return,
+                    _ => {}
+                },
+                _ = self.shutdown.requested() => return,
+            }
+        }
+    }
+
+    // Tell all subscribers that the server is going down
+    pub(crate) async fn notify_shutdown(&self, reason: ShutdownReason) {
+        let payload = match self.schema {
+            HealthSchema::Legacy => to_value(&Notification {
+                health: false,
+                reason: Some(reason.to_string()),
+            }),
+            HealthSchema::V2 => {
+                let sequence = {
+                    let mut pushed = self.pushed.lock().unwrap_or_else(|e| e.into_inner());
+                    pushed.sequence += 1;
+                    pushed.sequence
+                };
+                let mut payload = self
+                    .payload(HealthStatus::Unhealthy, sequence, 0, self.clock.now())
+                    .await;
+                payload.reasons = vec![reason.to_string()];
+                to_value(&payload)
+            }
+        };
+
+        info!(
+            "Sending the final notification to all subscribers: {}",
+            reason
+        );
+        self.send(&payload, EventType::HealthChanged, Some(SHUTDOWN_TIMEOUT))
+            .await;
+    }
+
+    // Push the health if `force`, or if it changed since the last push. Skipped until the
+    // first health check completes. Returns whether the health was pushed
+    async fn push(&self, force: bool) -> bool {
+        let Some(health) = *self.server_health.read().await else {
+            return false;
+        };
+        let issues = self.issues.read().await.clone();
+        let now = self.clock.now();
+
+        let (sequence, since_change, changed) = {
+            let mut pushed = self.pushed.lock().unwrap_or_else(|e| e.into_inner());
+            let state = (health, issues.clone());
+            let changed = pushed.last.as_ref() != Some(&state);
+            if !changed && !force {
+                return false;
+            }
+            if changed {
+                pushed.last = Some(state);
+                pushed.changed_at = Some(now);
+            }
+            pushed.sequence += 1;
+            let changed_at = pushed.changed_at.unwrap_or(now);
+            (pushed.sequence, seconds(now - changed_at), changed)
+        };
+
+        let payload = match self.schema {
+            HealthSchema::Legacy => to_value(&Notification::new(health, &issues)),
+            HealthSchema::V2 => {
+                let status = status(health, &issues);
+                let mut payload = self.payload(status, sequence, since_change, now).await;
+                let failure = self.stats.read().await.failure.filter(|_| !health);
+                payload.reasons = reasons(&issues, failure);
+                to_value(&payload)
+            }
+        };
+        let event_type = match changed {
+            true => EventType::HealthChanged,
+            false => EventType::HealthReported,
+        };
+        self.send(&payload, event_type, None).await;
+
+        true
+    }
+
+    // Versioned payload, without reasons
+    async fn payload(
+        &self,
+        status: HealthStatus,
+        sequence: u64,
+        since_change: u64,
+        now: DateTime<Utc>,
+    ) -> HealthPayload {
+        let stats = self.stats.read().await.clone();
+        let device_id = self
+            .node
+            .read()
+            .await
+            .as_ref()
+            .map(|node| node.device_id().to_string());
+        HealthPayload {
+            schema_version: HEALTH_SCHEMA_VERSION,
+            device_id,
+            assistant_version: env!("CARGO_PKG_VERSION"),
+            timestamp: timestamp(now),
+            sequence,
+            status,
+            reasons: Vec::new(),
+            since_change,
+            last_successful_probe: stats.last_successful_probe.map(timestamp),
+            requests: RequestStats {
+                total: stats.requests,
+                failed: stats.failed_requests,
+            },
+            uptime: Uptime {
+                assistant: seconds(now - self.started_at),
+                api_server: stats.up_since.map(|since| seconds(now - since)),
+            },
+        }
+    }
+
+    async fn send(&self, payload: &Value, event_type: EventType, timeout: Option<Duration>) {
+        let subs = self.subscribers.read().await;
+        match (subs.is_empty(), &self.dry_run) {
+            (true, _) => {
+                info!("Not found subscribers to notifications.");
+            }
+            (false, Some(dry_run)) => dry_run.write(Payload::DeviceHealth, subs.iter(), payload),
+            (false, None) => {
+                info!("Sending notifications to all subscribers...");
+
+                let payload = payload.to_string();
+                let device_id = self
+                    .node
+                    .read()
+                    .await
+                    .as_ref()
+                    .map(|node| node.device_id().to_string());
+                let message = self
+                    .delivery
+                    .message(event_type, device_id.as_deref(), &payload);
+                for url in subs.iter() {
+                    // Send POST request using reqwest
+                    let mut request = match self.delivery.post(url, &message) {
+                        Ok(request) => request,
+                        Err(e) => {
+                            error!("Failed to send notification to {}: {}", url, e);
+                            continue;
+                        }
+                    };
+                    if let Some(timeout) = timeout {
+                        request = request.timeout(timeout);
+                    }
+                    match request.send().await {
+                        Ok(response) => {
+                            if !response.status().is_success() {
+                                error!(
+                                    "Failed to send notification to {}. Status: {}",
+                                    url,
+                                    response.status()
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            error!("Error sending notification to {}: {}", url, e);
+                        }
+                    }
+                }
+
+                info!("Notification sent to all subscribers successfully!");
+            }
+        }
+    }
+}
+
+// Reasons of the versioned payload: the issues, and why the API server is unhealthy if it is
+fn reasons(issues: &BTreeSet<Issue>, failure: Option<Failure>) -> Vec<String> {
+    failure
+        .map(|failure| failure.reason())
+        .into_iter()
+        .chain(issues.iter().map(|issue| issue.reason()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+// Status of the versioned payload, the legacy `health` being false unless healthy or degraded
+fn status(health: bool, issues: &BTreeSet<Issue>) -> HealthStatus {
+    if !health || issues.iter().any(|issue| !issue.is_degraded()) {
+        HealthStatus::Unhealthy
+    } else if !issues.is_empty() {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Healthy
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn seconds(duration: chrono::Duration) -> u64 {
+    duration.num_seconds().max(0) as u64
+}
+
+fn to_value<T: Serialize>(payload: &T) -> Value {
+    serde_json::to_value(payload).unwrap_or_else(|e| {
+        error!("Failed to serialize the message: {}", e);
+        Value::Null
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consistency::Mismatch, models::ModelKind};
+
+    #[test]
+    fn reason_is_only_sent_when_going_down() {
+        let periodic = Notification::new(true, &BTreeSet::new());
+        let last = Notification {
+            health: false,
+            reason: Some(ShutdownReason::NodeStopping.to_string()),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&periodic).unwrap(),
+            serde_json::json!({ "health": true })
+        );
+        assert_eq!(
+            serde_json::to_value(&last).unwrap(),
+            serde_json::json!({ "health": false, "reason": "node_stopping" })
+        );
+    }
+
+    #[test]
+    fn legacy_payload_only_carries_the_health() {
+        let mut issues = BTreeSet::from([Issue::ConfigMismatch(Mismatch::PromptTemplate)]);
+        assert_eq!(
+            serde_json::to_value(Notification::new(true, &issues)).unwrap(),
+            serde_json::json!({ "health": true })
+        );
+
+        issues.insert(Issue::ModelHashMismatch(ModelKind::Chat));
+        assert_eq!(
+            serde_json::to_value(Notification::new(true, &issues)).unwrap(),
+            serde_json::json!({ "health": false })
+        );
+    }
+
+    #[test]
+    fn reasons_tell_why_the_server_is_unhealthy() {
+        let issues = BTreeSet::from([
+            Issue::ModelHashMismatch(ModelKind::Chat),
+            Issue::ModelHashMismatch(ModelKind::Embedding),
+            Issue::ConfigMismatch(Mismatch::PromptTemplate),
+        ]);
+        assert_eq!(
+            reasons(&issues, None),
+            ["model_hash_mismatch", "prompt_template_mismatch"]
+        );
+        assert_eq!(
+            reasons(&BTreeSet::new(), Some(Failure::Unreachable)),
+            ["api_server_unreachable"]
+        );
+        assert_eq!(
+            reasons(
+                &BTreeSet::from([Issue::AuthMisconfigured]),
+                Some(Failure::ProbeFailed)
+            ),
+            ["auth_misconfigured", "probe_failed"]
+        );
+    }
+
+    #[test]
+    fn status_follows_health_and_issues() {
+        let mut issues = BTreeSet::new();
+        assert_eq!(status(true, &issues), HealthStatus::Healthy);
+        assert_eq!(status(false, &issues), HealthStatus::Unhealthy);
+
+        issues.insert(Issue::ConfigMismatch(Mismatch::PromptTemplate));
+        assert_eq!(status(true, &issues), HealthStatus::Degraded);
+
+        issues.insert(Issue::AuthMisconfigured);
+        assert_eq!(status(true, &issues), HealthStatus::Unhealthy);
+    }
+}
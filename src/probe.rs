@@ -0,0 +1,259 @@
+//! One-shot health check of the API server, behind `gaias probe`.
+
+use crate::{
+    assistant::InfoExtras,
+    config::AssistantConfig,
+    consistency,
+    error::AssistantError,
+    gaianet::GaianetConfig,
+    health::{is_auth_error, is_healthy, HealthStatus, HttpProber, Prober},
+    http,
+    info::retrieve_server_info,
+    integrity::ModelsLock,
+    models::{node_models, Fingerprint, HashCache},
+};
+use serde::Serialize;
+use std::{fmt, path::Path, time::Duration};
+use tokio::time::Instant;
+
+// time given to the API server to answer a request
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Result of probing the API server once.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProbeReport {
+    pub status: HealthStatus,
+    /// Why the API server is degraded or unhealthy
+    pub reasons: Vec<String>,
+    /// Status code of the chat probe, if the API server answered
+    pub probe_status: Option<u16>,
+    /// Time taken by the chat probe
+    pub latency_ms: u64,
+    /// Details of the reasons
+    pub details: Vec<String>,
+}
+impl ProbeReport {
+    /// Exit code of the probe: 0, 1 and 2 for healthy, degraded and unhealthy, as for the
+    /// OK, WARNING and CRITICAL states of a Nagios plugin.
+    pub fn exit_code(&self) -> u8 {
+        match self.status {
+            HealthStatus::Healthy => 0,
+            HealthStatus::Degraded => 1,
+            HealthStatus::Unhealthy => 2,
+        }
+    }
+
+    /// Output in the Nagios plugin format, with the latency as performance data
+    pub fn nagios(&self) -> String {
+        let state = match self.status {
+            HealthStatus::Healthy => "OK",
+            HealthStatus::Degraded => "WARNING",
+            HealthStatus::Unhealthy => "CRITICAL",
+        };
+        let mut summary = self.status.to_string();
+        if !self.reasons.is_empty() {
+            summary = format!("{}: {}", summary, self.reasons.join(", "));
+        }
+        format!(
+            "GAIAS {} - {} | latency={:.3}s",
+            state,
+            summary,
+            self.latency_ms as f64 / 1000.0
+        )
+    }
+
+    fn add(&mut self, status: HealthStatus, reason: &str, detail: String) {
+        self.status = self.status.max(status);
+        if !self.reasons.iter().any(|known| known == reason) {
+            self.reasons.push(reason.to_string());
+        }
+        self.details.push(detail);
+    }
+}
+impl fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Status: {}", self.status)?;
+        if !self.reasons.is_empty() {
+            writeln!(f, "Reasons: {}", self.reasons.join(", "))?;
+        }
+        match self.probe_status {
+            Some(status) => writeln!(f, "Probe: status {} in {} ms", status, self.latency_ms)?,
+            None => writeln!(f, "Probe: no response in {} ms", self.latency_ms)?,
+        }
+        for detail in self.details.iter() {
+            writeln!(f, "- {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Send the chat probe to the API server, check its information against `config.json` and
+/// the cached hashes of the models against their expected sha256. Nothing is pushed.
+pub async fn probe_once(gaianet_dir: &Path, config: &AssistantConfig) -> ProbeReport {
+    let mut report = ProbeReport {
+        status: HealthStatus::Healthy,
+        reasons: Vec::new(),
+        probe_status: None,
+        latency_ms: 0,
+        details: Vec::new(),
+    };
+
+    let client = match http::api_client(&config.http, &config.server_socket_addr) {
+        Ok(client) => client,
+        Err(e) => {
+            report.add(
+                HealthStatus::Unhealthy,
+                "http_client_invalid",
+                e.to_string(),
+            );
+            return report;
+        }
+    };
+
+    let api_key = match http::api_key(config, gaianet_dir) {
+        Ok(api_key) => api_key,
+        Err(e) => {
+            report.add(HealthStatus::Unhealthy, "auth_misconfigured", e.to_string());
+            return report;
+        }
+    };
+
+    // chat probe
+    let addr = &config.server_socket_addr;
+    let prober = HttpProber::new(addr.clone(), config.probe.clone())
+        .with_client(client.clone())
+        .with_api_key(api_key.clone());
+    let start = Instant::now();
+    let result = match tokio::time::timeout(PROBE_TIMEOUT, prober.ping()).await {
+        Ok(result) => result,
+        Err(_) => Err(AssistantError::ServerDownError(format!(
+            "no response within {} seconds",
+            PROBE_TIMEOUT.as_secs()
+        ))),
+    };
+    report.latency_ms = start.elapsed().as_millis() as u64;
+    let healthy = is_healthy(&result);
+    match result {
+        Ok(response) => {
+            report.probe_status = Some(response.status.as_u16());
+            if is_auth_error(response.status) {
+                report.add(
+                    HealthStatus::Unhealthy,
+                    "auth_misconfigured",
+                    format!("status {}: {}", response.status, response.body.trim()),
+                );
+            } else if !healthy {
+                report.add(
+                    HealthStatus::Unhealthy,
+                    "vector_database_error",
+                    format!("status {}: {}", response.status, response.body.trim()),
+                );
+            }
+        }
+        Err(AssistantError::ServerDownError(e)) => {
+            report.add(HealthStatus::Unhealthy, "server_unreachable", e)
+        }
+        Err(e) if !healthy => report.add(
+            HealthStatus::Unhealthy,
+            "vector_database_error",
+            e.to_string(),
+        ),
+        Err(_) => {}
+    }
+
+    // the settings are only needed by the checks below
+    let Ok(gaianet) = GaianetConfig::load(&gaianet_dir.join("config.json")).await else {
+        return report;
+    };
+
+    if healthy {
+        let extras = InfoExtras::default();
+        let info = retrieve_server_info(addr, &extras, &client, api_key.as_deref());
+        if let Ok(Ok(server_info)) = tokio::time::timeout(PROBE_TIMEOUT, info).await {
+            for warning in consistency::check(&server_info, &gaianet) {
+                report.add(HealthStatus::Degraded, warning.reason, warning.message);
+            }
+        }
+    }
+
+    // models are not hashed, only the cached hashes of unchanged models are verified
+    let lock = ModelsLock::load(&gaianet_dir.join(&config.paths.models_lock)).unwrap_or_default();
+    let cache = HashCache::load(&gaianet_dir.join(&config.paths.hash_cache));
+    for model in node_models(gaianet_dir, &gaianet, &lock) {
+        let (Some(expected), Ok(fingerprint)) =
+            (&model.expected_sha256, Fingerprint::of(&model.path))
+        else {
+            continue;
+        };
+        if let Some(sha256) = cache.get(&model.path, &fingerprint) {
+            if !expected.eq_ignore_ascii_case(sha256) {
+                report.add(
+                    HealthStatus::Unhealthy,
+                    "model_hash_mismatch",
+                    format!(
+                        "{} model {}: expected {}, found {}",
+                        model.kind,
+                        model.path.display(),
+                        expected,
+                        sha256
+                    ),
+                );
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ProbeReport {
+        ProbeReport {
+            status: HealthStatus::Healthy,
+            reasons: Vec::new(),
+            probe_status: Some(200),
+            latency_ms: 1250,
+            details: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn worst_status_wins() {
+        let mut report = report();
+        report.add(
+            HealthStatus::Unhealthy,
+            "model_hash_mismatch",
+            String::new(),
+        );
+        report.add(
+            HealthStatus::Degraded,
+            "prompt_template_mismatch",
+            String::new(),
+        );
+
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        assert_eq!(report.exit_code(), 2);
+        assert_eq!(
+            report.reasons,
+            ["model_hash_mismatch", "prompt_template_mismatch"]
+        );
+    }
+
+    #[test]
+    fn nagios_output() {
+        let mut report = report();
+        assert_eq!(report.nagios(), "GAIAS OK - healthy | latency=1.250s");
+
+        report.add(
+            HealthStatus::Degraded,
+            "chat_ctx_size_mismatch",
+            String::new(),
+        );
+        assert_eq!(
+            report.nagios(),
+            "GAIAS WARNING - degraded: chat_ctx_size_mismatch | latency=1.250s"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use crate::{
+    assistant::{check_secrets, read_api_key, Assistant, Event},
+    config::{SubscriberConfig, Topic},
+    error::AssistantError,
+    gaianet::NodeConfig,
+    http,
+    hub::HubUrls,
+    integrity::ModelsLock,
+    models::{node_models, Fingerprint},
+    schedule::Schedules,
+};
+use log::{error, info, warn};
+use std::{fs, path::PathBuf, time::Duration, time::SystemTime};
+
+// interval of polling the watched files for changes
+pub(crate) const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+impl Assistant {
+    /// Load the configuration and the node settings again, and apply the changes.
+    ///
+    /// The tickers are re-armed if the schedules changed, and the server information is pushed
+    /// again if the hub URLs (from the domain, the device ID or the hub URL), the prompts or
+    /// the subscribers to the server information changed. The subscribers of the
+    /// configuration are added, removed or updated in place, secrets and encodings included.
+    /// The HTTP clients are rebuilt from the `[http]` settings, reading the certificates again,
+    /// and the API key of the API server is read again.
+    /// Models configured at a new path or URL are hashed again in the background, and only
+    /// verified once hashed. The server address, the
+    /// log files and the paths take effect after a restart.
+    pub async fn reload(&self) -> Result<(), AssistantError> {
+        info!("Reload the configuration");
+
+        let mut config = match &self.config_loader {
+            Some(loader) => loader()?,
+            None => self.config.read().await.clone(),
+        };
+        check_secrets(config.subscribers.iter().filter_map(|subscriber| {
+            subscriber
+                .secret
+                .as_deref()
+                .map(|secret| (subscriber.url.as_str(), secret))
+        }))?;
+        // the certificates are read again even if the settings are unchanged, as the files
+        // may have been replaced, e.g. when rotating the client certificate
+        let client = http::client(&config.http)?;
+        let api_client = http::api_client(&config.http, &self.server_addr)?;
+        {
+            let current = self.config.read().await;
+            if config.server_socket_addr != current.server_socket_addr
+                || config.log != current.log
+                || config.paths != current.paths
+                || config.notifications != current.notifications
+            {
+                warn!("Changes of server_socket_addr, log, paths and notifications take effect after a restart");
+            }
+            config.server_socket_addr = current.server_socket_addr.clone();
+            config.log = current.log.clone();
+            config.paths = current.paths.clone();
+            config.notifications = current.notifications.clone();
+        }
+        // the key file is read again, e.g. after the key was rotated
+        let api_key = read_api_key(&config, self.gaianet_dir.as_deref())?;
+
+        // apply the node settings
+        let mut info_changed = false;
+        let mut moved = Vec::new();
+        if let Some(gaianet_dir) = &self.gaianet_dir {
+            let node = NodeConfig::load(gaianet_dir, &config.paths).await?;
+            let lock = ModelsLock::load(&gaianet_dir.join(&config.paths.models_lock))?;
+            let models = node_models(gaianet_dir, &node.config, &lock);
+            let hub_urls = HubUrls::new(&config.hub, &node).map_err(|e| {
+                let err_msg = format!("Invalid hub URL {}", e);
+                error!("{}", &err_msg);
+                AssistantError::ConfigError(err_msg)
+            })?;
+
+            // the server information is checked against the node settings
+            let mut current_node = self.node.write().await;
+            if current_node.as_ref() != Some(&node) {
+                *current_node = Some(node.clone());
+                info_changed = true;
+            }
+            drop(current_node);
+
+            let mut current_urls = self.hub_urls.write().await;
+            if current_urls.as_ref() != Some(&hub_urls) {
+                let mut info_subscribers = self.info_subscribers.write().await;
+                let mut health_subscribers = self.health_subscribers.write().await;
+                if let Some(old) = current_urls.as_ref() {
+                    for url in old.info.iter() {
+                        info!("Remove subscriber for server info: {}", url);
+                        info_subscribers.remove(url);
+                    }
+                    for url in old.health.iter() {
+                        info!("Remove subscriber for server health: {}", url);
+                        health_subscribers.remove(url);
+                    }
+                }
+                for url in hub_urls.info.iter() {
+                    info!("Add subscriber for server info: {}", url);
+                    info_subscribers.insert(url.clone());
+                }
+                for url in hub_urls.health.iter() {
+                    info!("Add subscriber for server health: {}", url);
+                    health_subscribers.insert(url.clone());
+                }
+
+                *current_urls = Some(hub_urls);
+                info_changed = true;
+            }
+
+            let mut extras = self.extras.write().await;
+            let system_prompt = self
+                .system_prompt
+                .clone()
+                .unwrap_or(node.config.system_prompt);
+            let rag_prompt = self.rag_prompt.clone().unwrap_or(node.config.rag_prompt);
+            if extras.system_prompt != system_prompt || extras.rag_prompt != rag_prompt {
+                info!("System prompt: {}", &system_prompt);
+                info!("RAG prompt: {}", &rag_prompt);
+                extras.system_prompt = system_prompt;
+                extras.rag_prompt = rag_prompt;
+                info_changed = true;
+            }
+
+            let mut current_models = self.models.write().await;
+            if *current_models != models {
+                // the hash and the metadata of the previous file no longer apply
+                for model in current_models.iter() {
+                    if !models.iter().any(|new| new.kind == model.kind) {
+                        info!("The {} model is no longer configured", model.kind);
+                        extras.set_sha256(model.kind, String::new());
+                        extras.set_gguf(model.kind, None);
+                        info_changed = true;
+                    }
+                }
+                for model in models.iter() {
+                    let unchanged = current_models.iter().any(|current| {
+                        current.kind == model.kind
+                            && current.path == model.path
+                            && current.url == model.url
+                    });
+                    if !unchanged {
+                        info!("The {} model is now {}", model.kind, model.url);
+                        extras.set_sha256(model.kind, String::new());
+                        extras.set_gguf(model.kind, None);
+                        moved.push(model.clone());
+                        info_changed = true;
+                    }
+                }
+                *current_models = models;
+            }
+        }
+        for model in moved.iter() {
+            self.clear_integrity(model.kind).await;
+        }
+        self.check_models().await;
+
+        // apply the subscribers besides the hubs
+        if self.update_subscribers(&config.subscribers).await {
+            info_changed = true;
+        }
+
+        // apply the configuration
+        self.delivery.set_client(client);
+        *self.api_client.write().await = api_client;
+        let api_key_changed = {
+            let mut current = self.api_key.write().await;
+            let changed = *current != api_key;
+            *current = api_key;
+            changed
+        };
+        if api_key_changed {
+            // the server information tells whether the new key is accepted
+            info!("The API key of the API server changed");
+            info_changed = true;
+        }
+        *self.probe.write().await = config.probe.clone();
+        let schedules = Schedules::new(&config);
+        let schedules_changed = {
+            let mut current = self.schedules.write().await;
+            let changed = *current != schedules;
+            *current = schedules;
+            changed
+        };
+        if schedules_changed {
+            info!("Schedules: {}", self.schedules.read().await);
+            self.rearm.notify_waiters();
+        }
+        *self.config.write().await = config;
+
+        let _ = self.events.send(Event::Reloaded);
+
+        let pushed = match info_changed {
+            true => self.refresh_info().await,
+            false => Ok(()),
+        };
+
+        // hashed once pushed without the previous hashes, so that the new ones are pushed last
+        if !moved.is_empty() {
+            let assistant = self.clone();
+            tokio::spawn(async move {
+                for model in moved.iter() {
+                    let fingerprint = Fingerprint::of(&model.path).ok();
+                    assistant.model_changed(model, fingerprint).await;
+                }
+            });
+        }
+
+        pushed
+    }
+
+    // Replace the subscribers of the configuration with `subscribers`, keeping the hubs.
+    // Returns whether a subscriber to the server information was added
+    async fn update_subscribers(&self, subscribers: &[SubscriberConfig]) -> bool {
+        let current = self.config.read().await.subscribers.clone();
+        let removed: Vec<_> = current
+            .iter()
+            .filter(|subscriber| !subscribers.contains(subscriber))
+            .collect();
+        let added: Vec<_> = subscribers
+            .iter()
+            .filter(|subscriber| !current.contains(subscriber))
+            .collect();
+        if removed.is_empty() && added.is_empty() {
+            return false;
+        }
+
+        let hub_urls = self.hub_urls.read().await.clone().unwrap_or(HubUrls {
+            info: Vec::new(),
+            health: Vec::new(),
+        });
+        let mut info_subscribers = self.info_subscribers.write().await;
+        let mut health_subscribers = self.health_subscribers.write().await;
+        for subscriber in removed.iter() {
+            let url = &subscriber.url;
+            if subscriber.topics.contains(&Topic::Info) && !hub_urls.info.contains(url) {
+                info!("Remove subscriber for server info: {}", url);
+                info_subscribers.remove(url);
+            }
+            if subscriber.topics.contains(&Topic::Health) && !hub_urls.health.contains(url) {
+                info!("Remove subscriber for server health: {}", url);
+                health_subscribers.remove(url);
+            }
+        }
+        let mut info_added = false;
+        for subscriber in added.iter() {
+            let url = &subscriber.url;
+            if subscriber.topics.contains(&Topic::Info) {
+                info!("Add subscriber for server info: {}", url);
+                info_added |= info_subscribers.insert(url.clone());
+            }
+            if subscriber.topics.contains(&Topic::Health) {
+                info!("Add subscriber for server health: {}", url);
+                health_subscribers.insert(url.clone());
+            }
+        }
+        self.delivery.update_subscribers(&removed, &added);
+
+        info_added
+    }
+
+    // Reload on SIGHUP, or when a watched file changes
+    pub(crate) async fn watch_for_reload(self) -> Result<(), AssistantError> {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .map_err(|e| {
+                let err_msg = format!("Failed to listen to SIGHUP: {}", e);
+                error!("{}", &err_msg);
+                AssistantError::Operation(err_msg)
+            })?;
+
+        for path in self.watched_files.iter() {
+            info!("Watch for changes: {}", path.display());
+        }
+        let mut modified = snapshot(&self.watched_files);
+        loop {
+            #[cfg(unix)]
+            let signaled = tokio::select! {
+                _ = hangup.recv() => {
+                    info!("Received SIGHUP");
+                    true
+                }
+                _ = self.clock.sleep(WATCH_INTERVAL) => false,
+            };
+            #[cfg(not(unix))]
+            let signaled = {
+                self.clock.sleep(WATCH_INTERVAL).await;
+                false
+            };
+
+            let current = snapshot(&self.watched_files);
+            let changed = current != modified;
+            if changed {
+                info!("Watched files changed");
+            }
+            modified = current;
+
+            if signaled || changed {
+                // keep the current settings if the new ones are invalid
+                if let Err(e) = self.reload().await {
+                    error!("Failed to reload: {}", e);
+                }
+            }
+        }
+    }
+}
+
+// Modification time and size of each file, `None` if missing
+fn snapshot(files: &[PathBuf]) -> Vec<Option<(SystemTime, u64)>> {
+    files
+        .iter()
+        .map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+                .ok()
+        })
+        .collect()
+}
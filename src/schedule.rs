@@ -0,0 +1,386 @@
+//! Schedules of the periodic tasks: an interval or a cron expression, with jitter.
+
+use crate::config::AssistantConfig;
+use chrono::{DateTime, Utc};
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr, time::Duration};
+
+/// When a periodic task runs, parsed from:
+///
+/// - an interval: seconds, or a number with the unit `s`, `m`, `h` or `d`, e.g. `30s`, `5m`
+/// - a cron expression in UTC, with 5 fields, or 6 starting with the seconds,
+///   e.g. `*/5 * * * *`
+/// - `off`, the task never runs
+///
+/// followed by an optional jitter, e.g. `5m ~30s` or `0 * * * * ~10m`: each run is delayed
+/// by a random duration up to the jitter, so that nodes sharing a schedule do not run at the
+/// same second.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    trigger: Trigger,
+    jitter: Duration,
+}
+impl Schedule {
+    /// Run every `interval`, without jitter. A zero interval never runs
+    pub fn every(interval: Duration) -> Self {
+        let trigger = match interval.is_zero() {
+            true => Trigger::Off,
+            false => Trigger::Every(interval),
+        };
+        Self {
+            trigger,
+            jitter: Duration::ZERO,
+        }
+    }
+
+    /// Never run
+    pub fn off() -> Self {
+        Self::every(Duration::ZERO)
+    }
+
+    /// Whether the task never runs
+    pub fn is_off(&self) -> bool {
+        self.trigger == Trigger::Off
+    }
+
+    /// Time of the next run after `after`, jitter included. None if the task never runs
+    pub fn next(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.due(after)?
+            .checked_add_signed(chrono::Duration::from_std(self.delay()).ok()?)
+    }
+
+    // Time of the next run after `after`, without jitter
+    pub(crate) fn due(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match &self.trigger {
+            Trigger::Off => None,
+            Trigger::Every(interval) => {
+                after.checked_add_signed(chrono::Duration::from_std(*interval).ok()?)
+            }
+            Trigger::Cron(_, cron) => cron.find_next_occurrence(&after, false).ok(),
+        }
+    }
+
+    // Random delay of a run, up to the jitter
+    pub(crate) fn delay(&self) -> Duration {
+        jitter(self.jitter)
+    }
+}
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (spec, jitter) = match s.split_once('~') {
+            Some((spec, jitter)) => (spec.trim(), parse_duration(jitter.trim())?),
+            None => (s.trim(), Duration::ZERO),
+        };
+
+        let trigger = if spec == "off" {
+            Trigger::Off
+        } else if spec.contains(char::is_whitespace) {
+            let cron = Cron::new(spec)
+                .with_seconds_optional()
+                .parse()
+                .map_err(|e| format!("invalid cron expression {}: {}", spec, e))?;
+            Trigger::Cron(spec.to_string(), Box::new(cron))
+        } else {
+            match parse_duration(spec)? {
+                interval if interval.is_zero() => Trigger::Off,
+                interval => Trigger::Every(interval),
+            }
+        };
+
+        Ok(Self { trigger, jitter })
+    }
+}
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.trigger {
+            Trigger::Off => write!(f, "off")?,
+            Trigger::Every(interval) => write!(f, "{}", format_duration(*interval))?,
+            Trigger::Cron(expression, _) => write!(f, "{}", expression)?,
+        }
+        if !self.jitter.is_zero() {
+            write!(f, " ~{}", format_duration(self.jitter))?;
+        }
+        Ok(())
+    }
+}
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self {
+        schedule.to_string()
+    }
+}
+
+// heartbeat of the health push on change
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(5 * 60);
+
+// Schedules of the tasks, with the defaults of the older settings applied
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Schedules {
+    log_scan: Schedule,
+    probe: Schedule,
+    health_push: Schedule,
+    info_refresh: Schedule,
+    model_rehash: Schedule,
+    heartbeat: Schedule,
+}
+impl Schedules {
+    pub(crate) fn new(config: &AssistantConfig) -> Self {
+        let interval = Schedule::every(Duration::from_secs(config.interval));
+        let schedules = &config.schedules;
+        Self {
+            log_scan: schedules.log_scan.clone().unwrap_or(interval.clone()),
+            probe: schedules.probe.clone().unwrap_or_else(|| {
+                Schedule::every(Duration::from_secs(config.probe.max_time_span.max(0) as u64))
+            }),
+            health_push: schedules.health_push.clone().unwrap_or(interval),
+            info_refresh: schedules.info_refresh.clone().unwrap_or_else(Schedule::off),
+            model_rehash: schedules.model_rehash.clone().unwrap_or_else(|| {
+                Schedule::every(Duration::from_secs(config.models.verify_interval))
+            }),
+            heartbeat: schedules
+                .heartbeat
+                .clone()
+                .unwrap_or_else(|| Schedule::every(DEFAULT_HEARTBEAT)),
+        }
+    }
+
+    pub(crate) fn get(&self, task: Task) -> &Schedule {
+        match task {
+            Task::LogScan => &self.log_scan,
+            Task::Probe => &self.probe,
+            Task::HealthPush => &self.health_push,
+            Task::InfoRefresh => &self.info_refresh,
+            Task::ModelRehash => &self.model_rehash,
+            Task::Heartbeat => &self.heartbeat,
+        }
+    }
+}
+impl fmt::Display for Schedules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "log scan {}, probe {}, health push {}, info refresh {}, model re-hash {}, heartbeat {}",
+            self.log_scan,
+            self.probe,
+            self.health_push,
+            self.info_refresh,
+            self.model_rehash,
+            self.heartbeat
+        )
+    }
+}
+
+// Periodic tasks of the assistant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Task {
+    LogScan,
+    Probe,
+    HealthPush,
+    InfoRefresh,
+    ModelRehash,
+    Heartbeat,
+}
+
+#[derive(Debug, Clone)]
+enum Trigger {
+    Off,
+    Every(Duration),
+    // the expression is kept to compare and render the schedule
+    Cron(String, Box<Cron>),
+}
+impl PartialEq for Trigger {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Trigger::Off, Trigger::Off) => true,
+            (Trigger::Every(a), Trigger::Every(b)) => a == b,
+            (Trigger::Cron(a, _), Trigger::Cron(b, _)) => a == b,
+            _ => false,
+        }
+    }
+}
+impl Eq for Trigger {}
+
+const UNITS: [(&str, u64); 4] = [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+
+// longest interval or jitter, 100 years, far within the range of the run times
+const MAX_DURATION_SECS: u64 = 36500 * 86400;
+
+// `90`, `90s`, `5m`, `2h` or `1d`
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => s.split_at(pos),
+        None => (s, "s"),
+    };
+    let invalid = || {
+        format!(
+            "invalid duration {}, expected e.g. 90, 30s, 5m, 2h or 1d",
+            s
+        )
+    };
+    let secs = UNITS
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, secs)| *secs)
+        .ok_or_else(invalid)?;
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    number
+        .checked_mul(secs)
+        .filter(|secs| *secs <= MAX_DURATION_SECS)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+// the largest unit dividing the duration
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    UNITS
+        .iter()
+        .find(|(_, unit)| secs > 0 && secs.is_multiple_of(*unit))
+        .map(|(name, unit)| format!("{}{}", secs / unit, name))
+        .unwrap_or_else(|| format!("{}s", secs))
+}
+
+// random duration up to `max`
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let mut bytes = [0u8; 8];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        return Duration::ZERO;
+    }
+    let millis = u64::try_from(max.as_millis()).unwrap_or(u64::MAX);
+    Duration::from_millis(u64::from_le_bytes(bytes) % millis.saturating_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, min: u32, sec: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 8, 1, hour, min, sec).unwrap()
+    }
+
+    #[test]
+    fn intervals_are_parsed() {
+        for (s, secs) in [
+            ("90", 90),
+            ("30s", 30),
+            ("5m", 300),
+            ("2h", 7200),
+            ("1d", 86400),
+        ] {
+            let schedule: Schedule = s.parse().unwrap();
+            assert_eq!(
+                schedule,
+                Schedule::every(Duration::from_secs(secs)),
+                "{}",
+                s
+            );
+        }
+        assert!("0".parse::<Schedule>().unwrap().is_off());
+        assert!("off".parse::<Schedule>().unwrap().is_off());
+        for s in ["", "5 minutes", "5x", "-5m", "5m ~soon"] {
+            assert!(s.parse::<Schedule>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn next_run_follows_the_trigger() {
+        let schedule: Schedule = "5m".parse().unwrap();
+        assert_eq!(schedule.next(at(10, 2, 30)), Some(at(10, 7, 30)));
+
+        let schedule: Schedule = "*/15 * * * *".parse().unwrap();
+        assert_eq!(schedule.next(at(10, 2, 30)), Some(at(10, 15, 0)));
+        assert_eq!(schedule.next(at(10, 15, 0)), Some(at(10, 30, 0)));
+
+        // with seconds
+        let schedule: Schedule = "30 0 * * * *".parse().unwrap();
+        assert_eq!(schedule.next(at(10, 2, 30)), Some(at(11, 0, 30)));
+
+        assert_eq!(Schedule::off().next(at(10, 0, 0)), None);
+        assert!("61 * * * *".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn jitter_delays_up_to_its_maximum() {
+        let schedule: Schedule = "0 * * * * ~10m".parse().unwrap();
+        for _ in 0..100 {
+            let next = schedule.next(at(10, 2, 30)).unwrap();
+            assert!(next >= at(11, 0, 0) && next <= at(11, 10, 0), "{}", next);
+        }
+
+        let schedule: Schedule = "30s~5s".parse().unwrap();
+        let runs: Vec<_> = (0..100)
+            .map(|_| schedule.next(at(10, 0, 0)).unwrap())
+            .collect();
+        assert!(runs
+            .iter()
+            .all(|next| *next >= at(10, 0, 30) && *next <= at(10, 0, 35)));
+        assert!(runs.iter().any(|next| *next != runs[0]));
+    }
+
+    #[test]
+    fn overflowing_durations_are_rejected() {
+        for s in [
+            "9999999999999999d",
+            "9999999999d",
+            "36501d",
+            "1m ~1000000000d",
+        ] {
+            let err = s.parse::<Schedule>().unwrap_err();
+            assert!(err.contains("invalid duration"), "{}", err);
+        }
+
+        // the longest durations are still scheduled
+        let schedule: Schedule = "36500d ~36500d".parse().unwrap();
+        assert!(schedule
+            .next(at(10, 0, 0))
+            .is_some_and(|next| next > at(10, 0, 0)));
+    }
+
+    #[test]
+    fn older_settings_are_the_defaults() {
+        let mut config = AssistantConfig {
+            interval: 15,
+            ..Default::default()
+        };
+        let schedules = Schedules::new(&config);
+        assert_eq!(schedules.get(Task::LogScan).to_string(), "15s");
+        assert_eq!(schedules.get(Task::Probe).to_string(), "30s");
+        assert_eq!(schedules.get(Task::HealthPush).to_string(), "15s");
+        assert!(schedules.get(Task::InfoRefresh).is_off());
+        assert_eq!(schedules.get(Task::ModelRehash).to_string(), "1d");
+        assert_eq!(schedules.get(Task::Heartbeat).to_string(), "5m");
+
+        config.schedules.health_push = Some("*/5 * * * * ~1m".parse().unwrap());
+        config.models.verify_interval = 0;
+        let schedules = Schedules::new(&config);
+        assert_eq!(schedules.get(Task::LogScan).to_string(), "15s");
+        assert_eq!(
+            schedules.get(Task::HealthPush).to_string(),
+            "*/5 * * * * ~1m"
+        );
+        assert!(schedules.get(Task::ModelRehash).is_off());
+    }
+
+    #[test]
+    fn schedules_round_trip() {
+        for s in ["30s", "5m ~30s", "*/5 * * * *", "0 0 * * * * ~1h", "off"] {
+            assert_eq!(s.parse::<Schedule>().unwrap().to_string(), s);
+        }
+        assert_eq!("90".parse::<Schedule>().unwrap().to_string(), "90s");
+        assert_eq!("120".parse::<Schedule>().unwrap().to_string(), "2m");
+    }
+}
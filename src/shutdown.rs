@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::sync::watch;
+
+/// Time allowed for in-flight pushes and the final notification when shutting down
+pub(crate) const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reason sent to the health subscribers in the final notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownReason {
+    /// The assistant stops, the node may keep running
+    AssistantShutdown,
+    /// The whole node is stopping
+    NodeStopping,
+}
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownReason::AssistantShutdown => write!(f, "assistant_shutdown"),
+            ShutdownReason::NodeStopping => write!(f, "node_stopping"),
+        }
+    }
+}
+
+/// Shutdown request shared by the tasks of the assistant.
+#[derive(Clone)]
+pub(crate) struct Shutdown {
+    requested: Arc<watch::Sender<Option<ShutdownReason>>>,
+}
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        Self {
+            requested: Arc::new(watch::Sender::new(None)),
+        }
+    }
+
+    /// Request the shutdown. The first reason requested is kept
+    pub(crate) fn request(&self, reason: ShutdownReason) {
+        self.requested
+            .send_if_modified(|requested| match requested {
+                Some(_) => false,
+                None => {
+                    *requested = Some(reason);
+                    true
+                }
+            });
+    }
+
+    /// Whether the shutdown is requested, for blocking work checking it periodically
+    pub(crate) fn is_requested(&self) -> bool {
+        self.requested.borrow().is_some()
+    }
+
+    /// Wait until the shutdown is requested, returning immediately if it already was
+    pub(crate) async fn requested(&self) -> ShutdownReason {
+        let mut receiver = self.requested.subscribe();
+        let reason = match receiver.wait_for(Option::is_some).await {
+            Ok(reason) => *reason,
+            // the sender lives as long as self
+            Err(_) => unreachable!(),
+        };
+        reason.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn first_requested_reason_wins() {
+        let shutdown = Shutdown::new();
+        let waiting = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.requested().await }
+        });
+
+        shutdown.request(ShutdownReason::NodeStopping);
+        shutdown.request(ShutdownReason::AssistantShutdown);
+
+        assert_eq!(waiting.await.unwrap(), ShutdownReason::NodeStopping);
+        assert_eq!(shutdown.requested().await, ShutdownReason::NodeStopping);
+    }
+}
@@ -0,0 +1,137 @@
+//! Webhook signatures of the payloads sent to subscribers other than the hub.
+//!
+//! A subscriber configured with a shared secret receives the header
+//! `X-Gaianet-Webhook-Signature: t=<timestamp>,v1=<signature>`, the signature being the
+//! hex-encoded HMAC-SHA256 of `{timestamp}.{body}` with the secret, as Stripe webhooks do.
+//! Signing the timestamp lets the subscriber reject replayed requests.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Header holding the timestamp and the HMAC-SHA256 signature of the body
+pub const SIGNATURE_HEADER: &str = "X-Gaianet-Webhook-Signature";
+/// Age beyond which [`verify`] rejects a signature by default
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+/// Value of the signature header for `body` sent at `timestamp`, in Unix seconds
+pub fn sign(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    let signature = mac(secret, timestamp, body).finalize().into_bytes();
+    format!("t={},v1={}", timestamp, hex::encode(signature))
+}
+
+/// Check the signature header of a request received at `now`, in Unix seconds.
+///
+/// Signatures older or further in the future than `tolerance` are rejected. Several `v1`
+/// signatures may be given, e.g. while the secret is rotated; one of them must match.
+pub fn verify(
+    secret: &[u8],
+    header: &str,
+    body: &[u8],
+    now: i64,
+    tolerance: Duration,
+) -> Result<(), String> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for (key, value) in header
+        .split(',')
+        .filter_map(|part| part.trim().split_once('='))
+    {
+        match key {
+            "t" => timestamp = value.parse::<i64>().ok(),
+            "v1" => signatures.push(value),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp.ok_or_else(|| "missing or invalid timestamp".to_string())?;
+    if signatures.is_empty() {
+        return Err("missing v1 signature".to_string());
+    }
+    if now.abs_diff(timestamp) > tolerance.as_secs() {
+        return Err(format!(
+            "timestamp {} is outside the tolerance of {} seconds",
+            timestamp,
+            tolerance.as_secs()
+        ));
+    }
+
+    // compared in constant time
+    let matches = signatures.iter().any(|signature| {
+        hex::decode(signature)
+            .map(|signature| {
+                mac(secret, timestamp, body)
+                    .verify_slice(&signature)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    });
+    match matches {
+        true => Ok(()),
+        false => Err("no matching signature".to_string()),
+    }
+}
+
+fn mac(secret: &[u8], timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("invalid HMAC key");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"whsec_0123456789";
+    const BODY: &[u8] = br#"{"health":true}"#;
+
+    #[test]
+    fn signatures_are_verified() {
+        let header = sign(SECRET, 1721824000, BODY);
+        assert!(header.starts_with("t=1721824000,v1="), "{}", header);
+        assert_eq!(header.len(), "t=1721824000,v1=".len() + 64);
+
+        assert_eq!(
+            verify(SECRET, &header, BODY, 1721824060, DEFAULT_TOLERANCE),
+            Ok(())
+        );
+        assert!(verify(b"other", &header, BODY, 1721824060, DEFAULT_TOLERANCE).is_err());
+        assert!(verify(
+            SECRET,
+            &header,
+            br#"{"health":false}"#,
+            1721824060,
+            DEFAULT_TOLERANCE
+        )
+        .is_err());
+
+        // a rotated secret is accepted alongside the current one
+        let rotated = format!("{},v1={}", header, "00".repeat(32));
+        assert_eq!(
+            verify(SECRET, &rotated, BODY, 1721824000, DEFAULT_TOLERANCE),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn replays_are_rejected() {
+        let header = sign(SECRET, 1721824000, BODY);
+        let err = verify(SECRET, &header, BODY, 1721824000 + 301, DEFAULT_TOLERANCE).unwrap_err();
+        assert_eq!(
+            err,
+            "timestamp 1721824000 is outside the tolerance of 300 seconds"
+        );
+
+        // the timestamp is signed
+        let forged = header.replace("t=1721824000", "t=1721824301");
+        assert_eq!(
+            verify(SECRET, &forged, BODY, 1721824301, DEFAULT_TOLERANCE).unwrap_err(),
+            "no matching signature"
+        );
+        assert_eq!(
+            verify(SECRET, "v1=00", BODY, 1721824000, DEFAULT_TOLERANCE).unwrap_err(),
+            "missing or invalid timestamp"
+        );
+    }
+}
@@ -0,0 +1,711 @@
+//! Test harness for running `gaias` end to end: a mock LlamaEdge API server, a mock hub
+//! recording the notifications it receives, and a temporary gaianet directory.
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::{
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, UnixListener},
+    process::{Child, Command},
+    task::JoinHandle,
+    time::Instant,
+};
+
+pub const DEVICE_ID: &str = "device-0123456789abcdef";
+pub const DOMAIN: &str = "gaia.domains";
+/// Address of the node key in `set_node_key`.
+pub const NODE_ADDRESS: &str = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+/// File names of the models in `default_config`.
+pub const CHAT_MODEL: &str = "Meta-Llama-3-8B-Instruct-Q5_K_M.gguf";
+pub const EMBEDDING_MODEL: &str = "nomic-embed-text-v1.5.f16.gguf";
+
+/// Request received by a mock server.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// Response returned by a mock server.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: String,
+    pub latency: Duration,
+}
+impl MockResponse {
+    pub fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            body: body.to_string(),
+            latency: Duration::ZERO,
+        }
+    }
+
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+            latency: Duration::ZERO,
+        }
+    }
+}
+
+type Handler = Arc<dyn Fn(&RecordedRequest) -> MockResponse + Send + Sync>;
+
+/// Minimal HTTP/1.1 server, answering one request per connection.
+struct MockHttpServer {
+    // None when listening on a Unix domain socket
+    addr: Option<SocketAddr>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    task: JoinHandle<()>,
+}
+impl MockHttpServer {
+    async fn start(handler: Handler) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = Arc::clone(&requests);
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let handler = Arc::clone(&handler);
+                let recorded = Arc::clone(&recorded);
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, handler, recorded).await;
+                });
+            }
+        });
+
+        Self {
+            addr: Some(addr),
+            requests,
+            task,
+        }
+    }
+
+    async fn start_unix(handler: Handler, path: &Path) -> Self {
+        let listener = UnixListener::bind(path).unwrap();
+        let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = Arc::clone(&requests);
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let handler = Arc::clone(&handler);
+                let recorded = Arc::clone(&recorded);
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, handler, recorded).await;
+                });
+            }
+        });
+
+        Self {
+            addr: None,
+            requests,
+            task,
+        }
+    }
+
+    fn addr(&self) -> SocketAddr {
+        self.addr
+            .expect("the mock server listens on a Unix domain socket")
+    }
+
+    fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+impl Drop for MockHttpServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    handler: Handler,
+    recorded: Arc<Mutex<Vec<RecordedRequest>>>,
+) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let header_end = loop {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    let request = RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    let response = handler(&request);
+    recorded.lock().unwrap().push(request);
+
+    if !response.latency.is_zero() {
+        tokio::time::sleep(response.latency).await;
+    }
+
+    let raw = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.body.len(),
+        response.body
+    );
+    stream.write_all(raw.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Reply of the mock API server to `/v1/chat/completions`.
+#[derive(Debug, Clone)]
+pub enum ChatReply {
+    Ok,
+    Status(u16, String),
+    QdrantError,
+}
+
+#[derive(Debug)]
+struct ApiState {
+    info: Value,
+    info_status: u16,
+    chat: ChatReply,
+    latency: Duration,
+    // bearer token required by all the endpoints, if any
+    api_key: Option<String>,
+}
+
+/// Mock LlamaEdge API server serving `/v1/info`, `/v1/models` and `/v1/chat/completions`.
+pub struct MockApiServer {
+    server: MockHttpServer,
+    state: Arc<Mutex<ApiState>>,
+    socket: Option<PathBuf>,
+}
+impl MockApiServer {
+    pub async fn start() -> Self {
+        Self::with_info(default_server_info()).await
+    }
+
+    pub async fn with_info(info: Value) -> Self {
+        Self::listen(info, None).await
+    }
+
+    /// Listen on a Unix domain socket instead of a TCP port
+    pub async fn start_unix(path: &Path) -> Self {
+        Self::listen(default_server_info(), Some(path.to_path_buf())).await
+    }
+
+    async fn listen(info: Value, socket: Option<PathBuf>) -> Self {
+        let state = Arc::new(Mutex::new(ApiState {
+            info,
+            info_status: 200,
+            chat: ChatReply::Ok,
+            latency: Duration::ZERO,
+            api_key: None,
+        }));
+
+        let handler_state = Arc::clone(&state);
+        let handler: Handler = Arc::new(move |request| {
+            let state = handler_state.lock().unwrap();
+            if let Some(api_key) = &state.api_key {
+                if request.header("authorization") != Some(&format!("Bearer {}", api_key)) {
+                    return MockResponse::text(401, "Invalid API key");
+                }
+            }
+            let mut response = match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/v1/info") => MockResponse::json(state.info_status, state.info.clone()),
+                ("GET", "/v1/models") => MockResponse::json(
+                    200,
+                    json!({
+                        "object": "list",
+                        "data": [{
+                            "id": state.info["chat_model"]["name"],
+                            "created": 1721824000,
+                            "object": "model",
+                            "owned_by": "Not specified"
+                        }]
+                    }),
+                ),
+                ("POST", "/v1/chat/completions") => match &state.chat {
+                    ChatReply::Ok => MockResponse::json(
+                        200,
+                        json!({
+                            "id": "chatcmpl-1",
+                            "object": "chat.completion",
+                            "model": state.info["chat_model"]["name"],
+                            "choices": [{
+                                "index": 0,
+                                "message": { "role": "assistant", "content": "I am a Gaia node." },
+                                "finish_reason": "stop"
+                            }]
+                        }),
+                    ),
+                    ChatReply::Status(status, body) => MockResponse::text(*status, body.clone()),
+                    ChatReply::QdrantError => MockResponse::text(
+                        500,
+                        "Qdrant error: Failed to search points. Collection `default` doesn't exist!",
+                    ),
+                },
+                _ => MockResponse::text(404, "Not Found"),
+            };
+            response.latency = state.latency;
+            response
+        });
+
+        let server = match &socket {
+            Some(path) => MockHttpServer::start_unix(handler, path).await,
+            None => MockHttpServer::start(handler).await,
+        };
+        Self {
+            server,
+            state,
+            socket,
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.server.addr()
+    }
+
+    /// Address given to gaias: the socket address, or the Unix domain socket
+    pub fn endpoint(&self) -> String {
+        match (&self.server.addr, &self.socket) {
+            (Some(addr), _) => addr.to_string(),
+            (None, Some(path)) => format!("unix:{}", path.display()),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    pub fn set_chat_reply(&self, reply: ChatReply) {
+        self.state.lock().unwrap().chat = reply;
+    }
+
+    pub fn set_info_status(&self, status: u16) {
+        self.state.lock().unwrap().info_status = status;
+    }
+
+    pub fn set_api_key(&self, api_key: &str) {
+        self.state.lock().unwrap().api_key = Some(api_key.to_string());
+    }
+
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.server.requests()
+    }
+
+    pub fn chat_requests(&self) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path == "/v1/chat/completions")
+            .collect()
+    }
+}
+
+/// `/v1/info` response of a LlamaEdge RAG API server.
+pub fn default_server_info() -> Value {
+    json!({
+        "version": "0.14.3",
+        "api_server": {
+            "type": "rag",
+            "version": "0.9.3",
+            "plugin_version": "b3499 (commit d2b1e2e1)",
+            "port": "8080"
+        },
+        "chat_model": {
+            "name": "Llama-3-8B-Instruct",
+            "type": "chat",
+            "ctx_size": 16384,
+            "batch_size": 128,
+            "prompt_template": "Llama3Chat",
+            "n_predict": 1024,
+            "n_gpu_layers": 100,
+            "temperature": 1.0,
+            "top_p": 1.0,
+            "repeat_penalty": 1.1,
+            "presence_penalty": 0.0,
+            "frequency_penalty": 0.0
+        },
+        "embedding_model": {
+            "name": "nomic-embed-text-v1.5",
+            "type": "embedding",
+            "ctx_size": 8192,
+            "batch_size": 8192,
+            "prompt_template": "Embedding",
+            "n_predict": 1024,
+            "n_gpu_layers": 100,
+            "temperature": 1.0,
+            "top_p": 1.0,
+            "repeat_penalty": 1.1,
+            "presence_penalty": 0.0,
+            "frequency_penalty": 0.0
+        },
+        "extras": {}
+    })
+}
+
+/// Mock hub recording the `device-info` and `device-health` notifications it receives.
+pub struct MockHub {
+    server: MockHttpServer,
+    status: Arc<Mutex<u16>>,
+}
+impl MockHub {
+    pub async fn start() -> Self {
+        let status = Arc::new(Mutex::new(200));
+        let handler_status = Arc::clone(&status);
+        let handler: Handler = Arc::new(move |_| {
+            MockResponse::json(*handler_status.lock().unwrap(), json!({ "ok": true }))
+        });
+
+        Self {
+            server: MockHttpServer::start(handler).await,
+            status,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.server.addr())
+    }
+
+    pub fn set_status(&self, status: u16) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.server.requests()
+    }
+
+    pub fn device_info(&self) -> Vec<RecordedRequest> {
+        self.posts_to(&format!("/device-info/{}", DEVICE_ID))
+    }
+
+    pub fn device_health(&self) -> Vec<RecordedRequest> {
+        self.posts_to(&format!("/device-health/{}", DEVICE_ID))
+    }
+
+    pub fn posts_to(&self, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.method == "POST" && request.path == path)
+            .collect()
+    }
+}
+
+/// Temporary gaianet directory with `config.json`, `gaia-frp/frpc.toml` and
+/// `log/start-llamaedge.log`.
+pub struct GaianetDir {
+    dir: TempDir,
+}
+impl GaianetDir {
+    pub fn new() -> Self {
+        Self::with_config(default_config())
+    }
+
+    pub fn with_config(config: Value) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let gaianet = Self { dir };
+
+        gaianet.write(
+            "config.json",
+            &serde_json::to_string_pretty(&config).unwrap(),
+        );
+        gaianet.set_device_id(DEVICE_ID);
+        gaianet.write("log/start-llamaedge.log", "");
+
+        gaianet
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn join(&self, relative: &str) -> PathBuf {
+        self.dir.path().join(relative)
+    }
+
+    pub fn write(&self, relative: &str, content: &str) {
+        self.write_bytes(relative, content.as_bytes());
+    }
+
+    pub fn write_bytes(&self, relative: &str, content: &[u8]) {
+        let path = self.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    pub fn set_device_id(&self, device_id: &str) {
+        self.write(
+            "gaia-frp/frpc.toml",
+            &format!(
+                "serverAddr = \"{}\"\nserverPort = 7000\n\n[metadatas]\ndeviceId = \"{}\"\n",
+                DOMAIN, device_id
+            ),
+        );
+    }
+
+    /// Write `nodeid.json` and the keystore of the node key, encrypted with a cheap scrypt.
+    pub fn set_node_key(&self) {
+        self.write(
+            "nodeid.json",
+            &json!({
+                "address": NODE_ADDRESS,
+                "keystore": "keystore/node.json",
+                "password": "gaianet"
+            })
+            .to_string(),
+        );
+        self.write(
+            "keystore/node.json",
+            &json!({
+                "version": 3,
+                "address": &NODE_ADDRESS[2..],
+                "crypto": {
+                    "cipher": "aes-128-ctr",
+                    "cipherparams": { "iv": "83dbcc02d8ccb40e466191a123791e0e" },
+                    "ciphertext": "c523f751c64f0c3084e0004c1c0bf229d4ed21297d964b590915ca0b14c77609",
+                    "kdf": "scrypt",
+                    "kdfparams": {
+                        "n": 1024,
+                        "r": 8,
+                        "p": 1,
+                        "dklen": 32,
+                        "salt": "6c4e1d2f3a5b7c9d0e1f2a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f"
+                    },
+                    "mac": "cae40202cd5820de9edab9b9da600bc27c52638e530812d3cf1b555ed8fed700"
+                }
+            })
+            .to_string(),
+        );
+    }
+
+    pub fn remove(&self, relative: &str) {
+        std::fs::remove_file(self.join(relative)).unwrap();
+    }
+
+    /// Append a response record to the API server log, as written by LlamaEdge.
+    pub fn log_response(&self, status: u16) {
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(self.join("log/start-llamaedge.log"))
+            .unwrap();
+        writeln!(
+            file,
+            "[{}] [info] llama_api_server in llama-api-server/src/main.rs:520: response_status: {}",
+            chrono::Utc::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            status
+        )
+        .unwrap();
+    }
+}
+
+/// Minimal GGUF v3 model file, with string and u32 metadata and tensors of the given shapes.
+pub fn gguf_model(
+    strings: &[(&str, &str)],
+    numbers: &[(&str, u32)],
+    tensors: &[&[u64]],
+) -> Vec<u8> {
+    fn put_string(bytes: &mut Vec<u8>, value: &str) {
+        bytes.extend((value.len() as u64).to_le_bytes());
+        bytes.extend(value.as_bytes());
+    }
+
+    let mut bytes = b"GGUF".to_vec();
+    bytes.extend(3u32.to_le_bytes());
+    bytes.extend((tensors.len() as u64).to_le_bytes());
+    bytes.extend(((strings.len() + numbers.len()) as u64).to_le_bytes());
+    for (key, value) in strings {
+        put_string(&mut bytes, key);
+        bytes.extend(8u32.to_le_bytes());
+        put_string(&mut bytes, value);
+    }
+    for (key, value) in numbers {
+        put_string(&mut bytes, key);
+        bytes.extend(4u32.to_le_bytes());
+        bytes.extend(value.to_le_bytes());
+    }
+    for (index, dims) in tensors.iter().enumerate() {
+        put_string(&mut bytes, &format!("blk.{}.weight", index));
+        bytes.extend((dims.len() as u32).to_le_bytes());
+        for dim in dims.iter() {
+            bytes.extend(dim.to_le_bytes());
+        }
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(0u64.to_le_bytes());
+    }
+    bytes
+}
+
+/// `config.json` of a gaianet node running a RAG API server.
+pub fn default_config() -> Value {
+    json!({
+        "address": "0x0123456789abcdef0123456789abcdef01234567",
+        "chat": "https://huggingface.co/gaianet/Llama-3-8B-Instruct-GGUF/resolve/main/Meta-Llama-3-8B-Instruct-Q5_K_M.gguf",
+        "chat_ctx_size": "16384",
+        "domain": DOMAIN,
+        "embedding": "https://huggingface.co/gaianet/Nomic-embed-text-v1.5-Embedding-GGUF/resolve/main/nomic-embed-text-v1.5.f16.gguf",
+        "embedding_ctx_size": "8192",
+        "llamaedge_port": "8080",
+        "prompt_template": "llama-3-chat",
+        "rag_prompt": "Use the following pieces of context to answer the user's question.",
+        "system_prompt": "You are a helpful assistant."
+    })
+}
+
+/// A running `gaias` process, killed when dropped.
+pub struct Gaias {
+    child: Child,
+    log: PathBuf,
+    _log_dir: TempDir,
+}
+impl Gaias {
+    pub fn command(gaianet: &GaianetDir, api: &MockApiServer, hub: &MockHub) -> (Command, TempDir) {
+        Self::command_with_hub_url(gaianet, api, &hub.url())
+    }
+
+    pub fn command_with_hub_url(
+        gaianet: &GaianetDir,
+        api: &MockApiServer,
+        hub_url: &str,
+    ) -> (Command, TempDir) {
+        let log_dir = tempfile::tempdir().unwrap();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_gaias"));
+        command
+            .arg("--gaianet-dir")
+            .arg(gaianet.path())
+            .arg("--server-socket-addr")
+            .arg(api.endpoint())
+            .arg("--hub-url")
+            .arg(hub_url)
+            .arg("--log")
+            .arg(log_dir.path().join("assistant.log"))
+            .env("RUST_LOG", "info")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+
+        (command, log_dir)
+    }
+
+    /// Spawn `gaias` checking the health every second.
+    pub fn spawn(gaianet: &GaianetDir, api: &MockApiServer, hub: &MockHub) -> Self {
+        Self::spawn_with_args(gaianet, api, hub, &["--interval", "1"])
+    }
+
+    pub fn spawn_with_args(
+        gaianet: &GaianetDir,
+        api: &MockApiServer,
+        hub: &MockHub,
+        args: &[&str],
+    ) -> Self {
+        let (mut command, log_dir) = Self::command(gaianet, api, hub);
+        command.args(args);
+        Self::spawn_command(command, log_dir)
+    }
+
+    /// Spawn `gaias` with a command from [`Gaias::command`].
+    pub fn spawn_command(mut command: Command, log_dir: TempDir) -> Self {
+        Self {
+            child: command.spawn().expect("failed to spawn gaias"),
+            log: log_dir.path().join("assistant.log"),
+            _log_dir: log_dir,
+        }
+    }
+
+    /// Send a signal, such as `HUP`, to `gaias`.
+    pub fn signal(&self, name: &str) {
+        let pid = self.child.id().expect("gaias already exited");
+        let status = std::process::Command::new("kill")
+            .arg(format!("-{}", name))
+            .arg(pid.to_string())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// Content of the log file written by `gaias`.
+    pub fn log(&self) -> String {
+        std::fs::read_to_string(&self.log).unwrap_or_default()
+    }
+
+    /// Wait for `gaias` to exit within the timeout.
+    pub async fn wait(&mut self, timeout: Duration) -> Option<std::process::ExitStatus> {
+        tokio::time::timeout(timeout, self.child.wait())
+            .await
+            .ok()
+            .map(|status| status.unwrap())
+    }
+}
+
+/// Poll `condition` until it holds or the timeout expires.
+pub async fn wait_until<F: FnMut() -> bool>(timeout: Duration, mut condition: F) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    condition()
+}
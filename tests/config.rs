@@ -0,0 +1,120 @@
+mod common;
+
+use common::{GaianetDir, Gaias, MockApiServer, MockHub};
+use std::{process::Command, time::Duration};
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+fn config_show(gaianet: &GaianetDir, args: &[&str], envs: &[(&str, &str)]) -> toml::Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_gaias"))
+        .arg("--gaianet-dir")
+        .arg(gaianet.path())
+        .args(args)
+        .args(["config", "show"])
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    toml::from_str(&String::from_utf8(output.stdout).unwrap()).unwrap()
+}
+
+#[test]
+fn defaults_without_config_file() {
+    let gaianet = GaianetDir::new();
+
+    let config = config_show(&gaianet, &[], &[]);
+
+    assert_eq!(config["server_socket_addr"].as_str(), Some("0.0.0.0:8080"));
+    assert_eq!(config["interval"].as_integer(), Some(10));
+    assert_eq!(
+        config["hub"]["url"].as_str(),
+        Some("https://hub.domain.{domain}")
+    );
+    assert_eq!(
+        config["paths"]["server_log"].as_str(),
+        Some("log/start-llamaedge.log")
+    );
+}
+
+#[test]
+fn options_override_env_which_overrides_file() {
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        "interval = 30\n\n[hub]\nretries = 5\nurl = \"https://staging.{domain}\"\n\n[probe]\nmodel = \"Llama-3-8B\"\n",
+    );
+
+    let config = config_show(
+        &gaianet,
+        &["--interval", "2"],
+        &[("GAIAS_INTERVAL", "20"), ("GAIAS_HUB_RETRIES", "7")],
+    );
+
+    assert_eq!(config["interval"].as_integer(), Some(2));
+    assert_eq!(config["hub"]["retries"].as_integer(), Some(7));
+    assert_eq!(
+        config["hub"]["url"].as_str(),
+        Some("https://staging.{domain}")
+    );
+    assert_eq!(config["probe"]["model"].as_str(), Some("Llama-3-8B"));
+}
+
+#[test]
+fn explicit_config_file_must_exist() {
+    let gaianet = GaianetDir::new();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gaias"))
+        .arg("--gaianet-dir")
+        .arg(gaianet.path())
+        .arg("--config")
+        .arg(gaianet.join("missing.toml"))
+        .args(["config", "show"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.toml"));
+}
+
+#[test]
+fn invalid_config_file_is_rejected() {
+    let gaianet = GaianetDir::new();
+    gaianet.write("assistant.toml", "[probe]\nmax_time_span = \"soon\"\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gaias"))
+        .arg("--gaianet-dir")
+        .arg(gaianet.path())
+        .args(["config", "show"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("max_time_span"));
+}
+
+#[tokio::test]
+async fn probe_settings_are_read_from_config_file() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        "[probe]\nprompt = \"Are you there?\"\nmodel = \"Llama-3-8B\"\n",
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !api.chat_requests().is_empty()).await,
+        "API server not probed. gaias log:\n{}",
+        gaias.log()
+    );
+    let request = api.chat_requests()[0].json();
+    assert_eq!(request["messages"][0]["content"], "Are you there?");
+    assert_eq!(request["model"], "Llama-3-8B");
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{GaianetDir, Gaias, MockApiServer, MockHub, CHAT_MODEL, EMBEDDING_MODEL};
+use serde_json::{json, Value};
+use std::process::{Output, Stdio};
+
+async fn doctor(gaianet: &GaianetDir, api: &MockApiServer, hub: &MockHub, args: &[&str]) -> Output {
+    let (mut command, _log_dir) = Gaias::command(gaianet, api, hub);
+    command
+        .arg("doctor")
+        .args(args)
+        .stdout(Stdio::piped())
+        .output()
+        .await
+        .unwrap()
+}
+
+fn status_of(report: &Value, name: &str) -> String {
+    report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|check| check["name"] == name)
+        .unwrap_or_else(|| panic!("no {} check in {}", name, report))["status"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test]
+async fn healthy_node_passes() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let mut config = common::default_config();
+    config["chat_sha256"] = json!(sha256::digest("chat model"));
+    let gaianet = GaianetDir::with_config(config);
+    gaianet.write(CHAT_MODEL, "chat model");
+    gaianet.write(EMBEDDING_MODEL, "embedding model");
+
+    let output = doctor(&gaianet, &api, &hub, &["--json"]).await;
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["passed"], true, "{:#}", report);
+    assert_eq!(output.status.code(), Some(0));
+    for name in [
+        "config.json",
+        "domain",
+        "frpc.toml",
+        "device_id",
+        "chat_model",
+        "embedding_model",
+        "server_log",
+        "server_info",
+        "consistency",
+        "chat_probe",
+        "hub_urls",
+    ] {
+        assert_eq!(status_of(&report, name), "pass", "{}", name);
+    }
+    // nothing is pushed
+    assert!(hub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn broken_node_fails() {
+    let api = MockApiServer::start().await;
+    api.set_info_status(500);
+    let hub = MockHub::start().await;
+    let mut config = common::default_config();
+    config["chat_sha256"] = json!(sha256::digest("chat model"));
+    let gaianet = GaianetDir::with_config(config);
+    gaianet.write(CHAT_MODEL, "tampered chat model");
+    gaianet.remove("gaia-frp/frpc.toml");
+
+    let output = doctor(&gaianet, &api, &hub, &[]).await;
+
+    assert_eq!(output.status.code(), Some(3));
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("[FAIL] frpc.toml"), "{}", report);
+    assert!(report.contains("[SKIP] device_id"), "{}", report);
+    assert!(report.contains("[FAIL] chat_model"), "{}", report);
+    assert!(report.contains("[FAIL] embedding_model"), "{}", report);
+    assert!(report.contains("[FAIL] server_info"), "{}", report);
+    assert!(report.contains("[PASS] chat_probe"), "{}", report);
+    assert!(report.contains("[SKIP] hub_urls"), "{}", report);
+}
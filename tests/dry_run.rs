@@ -0,0 +1,136 @@
+mod common;
+
+use common::{GaianetDir, Gaias, MockApiServer, MockHub, DEVICE_ID};
+use serde_json::Value;
+use std::{path::Path, time::Duration};
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+fn records(path: &Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn of_type(records: &[Value], kind: &str) -> Vec<Value> {
+    records
+        .iter()
+        .filter(|record| record["type"] == kind)
+        .cloned()
+        .collect()
+}
+
+#[tokio::test]
+async fn payloads_are_written_instead_of_sent() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    let output = tempfile::tempdir().unwrap();
+    let payloads = output.path().join("payloads.jsonl");
+
+    let mut gaias = Gaias::spawn_with_args(
+        &gaianet,
+        &api,
+        &hub,
+        &["--interval", "1", "--dry-run", payloads.to_str().unwrap()],
+    );
+
+    assert!(
+        common::wait_until(TIMEOUT, || {
+            let records = records(&payloads);
+            !of_type(&records, "device-info").is_empty()
+                && !of_type(&records, "device-health").is_empty()
+        })
+        .await,
+        "no payloads written. gaias log:\n{}",
+        gaias.log()
+    );
+
+    let records = records(&payloads);
+    let info = &of_type(&records, "device-info")[0];
+    assert_eq!(info["valid"], true, "{:#}", info);
+    assert_eq!(
+        info["urls"][0],
+        format!("{}/device-info/{}", hub.url(), DEVICE_ID)
+    );
+    assert_eq!(info["payload"]["chat_model"]["name"], "Llama-3-8B-Instruct");
+    let health = &of_type(&records, "device-health")[0];
+    assert_eq!(health["valid"], true, "{:#}", health);
+    assert_eq!(health["payload"]["health"], true);
+
+    // the final notification is written too
+    gaias.signal("TERM");
+    assert_eq!(
+        gaias.wait(TIMEOUT).await.and_then(|status| status.code()),
+        Some(0)
+    );
+    let records = of_type(&self::records(&payloads), "device-health");
+    assert_eq!(
+        records.last().unwrap()["payload"]["reason"],
+        "assistant_shutdown"
+    );
+
+    // the models are probed and the server information retrieved, but nothing is sent
+    assert!(!api.chat_requests().is_empty());
+    assert!(hub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn versioned_health_payloads_are_valid() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write("assistant.toml", "[notifications]\nschema = \"v2\"\n");
+    gaianet.log_response(200);
+    let output = tempfile::tempdir().unwrap();
+    let payloads = output.path().join("payloads.jsonl");
+
+    let mut gaias = Gaias::spawn_with_args(
+        &gaianet,
+        &api,
+        &hub,
+        &["--interval", "1", "--dry-run", payloads.to_str().unwrap()],
+    );
+
+    assert!(
+        common::wait_until(TIMEOUT, || of_type(&records(&payloads), "device-health")
+            .len()
+            >= 2)
+        .await,
+        "no health payloads written. gaias log:\n{}",
+        gaias.log()
+    );
+
+    let records = of_type(&records(&payloads), "device-health");
+    let health = &records[0];
+    assert_eq!(health["valid"], true, "{:#}", health);
+    let payload = &health["payload"];
+    assert_eq!(payload["schema_version"], 2);
+    assert_eq!(payload["device_id"], DEVICE_ID);
+    assert_eq!(payload["assistant_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(payload["sequence"], 1);
+    assert_eq!(payload["status"], "healthy");
+    assert_eq!(payload["reasons"], serde_json::json!([]));
+    assert_eq!(
+        payload["requests"],
+        serde_json::json!({ "total": 1, "failed": 0 })
+    );
+    assert!(payload["uptime"]["api_server"].is_u64());
+    assert_eq!(records[1]["payload"]["sequence"], 2);
+
+    gaias.signal("TERM");
+    assert_eq!(
+        gaias.wait(TIMEOUT).await.and_then(|status| status.code()),
+        Some(0)
+    );
+    let records = of_type(&self::records(&payloads), "device-health");
+    let last = records.last().unwrap();
+    assert_eq!(last["valid"], true, "{:#}", last);
+    assert_eq!(last["payload"]["status"], "unhealthy");
+    assert_eq!(
+        last["payload"]["reasons"],
+        serde_json::json!(["assistant_shutdown"])
+    );
+}
@@ -0,0 +1,705 @@
+mod common;
+
+use common::{
+    ChatReply, GaianetDir, Gaias, MockApiServer, MockHub, RecordedRequest, DEVICE_ID, DOMAIN,
+    NODE_ADDRESS,
+};
+use serde_json::{json, Value};
+use server_assistant::{
+    identity::{PayloadSignature, ADDRESS_HEADER},
+    webhook,
+};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[tokio::test]
+async fn pushes_server_info_and_health_to_hub() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()
+            && !hub.device_health().is_empty())
+        .await,
+        "no notifications received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+
+    let info = hub.device_info()[0].json();
+    assert_eq!(info["api_server"]["type"], "rag");
+    assert_eq!(info["chat_model"]["name"], "Llama-3-8B-Instruct");
+    assert_eq!(
+        info["rag_prompt"],
+        "Use the following pieces of context to answer the user's question."
+    );
+    assert_eq!(
+        info["extras"]["system_prompt"],
+        "You are a helpful assistant."
+    );
+    assert!(info["hardware"].is_object());
+    assert_eq!(
+        hub.device_info()[0].header("content-type"),
+        Some("application/json")
+    );
+
+    // the log is empty, so the health is derived from a synthetic chat request
+    assert_eq!(hub.device_health()[0].json(), json!({ "health": true }));
+    let chat = api.chat_requests();
+    assert!(!chat.is_empty());
+    assert_eq!(
+        chat[0].json()["messages"][0]["content"],
+        "Who are you? <server-health>"
+    );
+}
+
+#[tokio::test]
+async fn health_is_pushed_periodically() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_health().len() >= 3).await,
+        "expected repeated health notifications. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_info().len(), 1);
+}
+
+#[tokio::test]
+async fn tasks_run_on_their_own_schedules() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        "[schedules]\nhealth_push = \"off\"\ninfo_refresh = \"* * * * * *\"\n",
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_info().len() >= 3).await,
+        "expected the server information to be refreshed every second. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(hub.device_health().is_empty());
+}
+
+#[tokio::test]
+async fn health_is_pushed_on_change_with_heartbeats() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        "[notifications]\nmode = \"on_change\"\nschema = \"v2\"\n\n[schedules]\nheartbeat = \"3s\"\n",
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_health().is_empty()).await,
+        "no health notification received. gaias log:\n{}",
+        gaias.log()
+    );
+    let health = hub.device_health()[0].json();
+    assert_eq!(
+        (
+            &health["status"],
+            &health["sequence"],
+            &health["since_change"]
+        ),
+        (&json!("healthy"), &json!(1), &json!(0))
+    );
+
+    // the change is pushed right away, without waiting for the heartbeat
+    gaianet.log_response(500);
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_health().len() >= 2).await,
+        "no health change received. gaias log:\n{}",
+        gaias.log()
+    );
+    let health = hub.device_health()[1].json();
+    assert_eq!(
+        (
+            &health["status"],
+            &health["sequence"],
+            &health["since_change"]
+        ),
+        (&json!("unhealthy"), &json!(2), &json!(0))
+    );
+    assert_eq!(health["reasons"], json!(["api_server_error"]));
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_health().len() >= 3).await,
+        "no heartbeat received. gaias log:\n{}",
+        gaias.log()
+    );
+    let heartbeat = hub.device_health()[2].json();
+    assert_eq!(heartbeat["status"], "unhealthy");
+    assert_eq!(heartbeat["sequence"], 3);
+    assert!(heartbeat["since_change"].as_u64().unwrap() >= 2);
+}
+
+#[tokio::test]
+async fn qdrant_error_reports_unhealthy() {
+    let api = MockApiServer::start().await;
+    api.set_chat_reply(ChatReply::QdrantError);
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_health().is_empty()).await,
+        "no health notification received. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_health()[0].json(), json!({ "health": false }));
+}
+
+#[tokio::test]
+async fn non_qdrant_probe_failure_keeps_healthy() {
+    let api = MockApiServer::start().await;
+    api.set_chat_reply(ChatReply::Status(503, "server busy".to_string()));
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_health().is_empty()).await,
+        "no health notification received. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_health()[0].json(), json!({ "health": true }));
+}
+
+#[tokio::test]
+async fn failed_response_in_log_reports_unhealthy() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.log_response(200);
+    gaianet.log_response(500);
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_health().is_empty()).await,
+        "no health notification received. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_health()[0].json(), json!({ "health": false }));
+    // the log already tells the health, so the API server is not probed
+    assert!(api.chat_requests().is_empty());
+}
+
+#[tokio::test]
+async fn slow_api_server_is_tolerated() {
+    let api = MockApiServer::start().await;
+    api.set_latency(Duration::from_millis(500));
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()
+            && !hub.device_health().is_empty())
+        .await,
+        "no notifications received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_health()[0].json(), json!({ "health": true }));
+}
+
+#[tokio::test]
+async fn health_is_reported_when_info_retrieval_fails() {
+    let api = MockApiServer::start().await;
+    api.set_info_status(500);
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_health().len() >= 2).await,
+        "no health notification received. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(hub.device_info().is_empty());
+    assert!(gaias
+        .log()
+        .contains("Failed to get server info from API Server. Status: 500"));
+}
+
+#[tokio::test]
+async fn exits_when_frpc_toml_is_missing() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.remove("gaia-frp/frpc.toml");
+
+    let mut gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let status = gaias.wait(TIMEOUT).await;
+    assert_eq!(status.and_then(|status| status.code()), Some(1));
+    assert!(gaias.log().contains("Invalid frpc.toml file path"));
+    assert!(hub.requests().is_empty());
+    assert!(api.requests().is_empty());
+}
+
+#[tokio::test]
+async fn exits_on_invalid_config_json() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let mut config = common::default_config();
+    config["chat_ctx_size"] = json!("16k");
+    let gaianet = GaianetDir::with_config(config);
+
+    let mut gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let status = gaias.wait(TIMEOUT).await;
+    assert_eq!(status.and_then(|status| status.code()), Some(1));
+    assert!(gaias
+        .log()
+        .contains("config.json: chat_ctx_size: invalid context size `16k`"));
+    assert!(hub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn device_id_is_read_from_frpc_toml() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.requests().is_empty()).await,
+        "no notifications received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(hub
+        .requests()
+        .iter()
+        .all(|request| request.path.ends_with(DEVICE_ID)));
+}
+
+#[tokio::test]
+async fn config_mismatch_degrades_health() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let mut config = common::default_config();
+    config["chat_ctx_size"] = json!("4096");
+    config["prompt_template"] = json!("phi-3-chat");
+    let gaianet = GaianetDir::with_config(config);
+    gaianet.write("assistant.toml", "[notifications]\nschema = \"v2\"\n");
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let degraded = |health: Value| {
+        health["status"] == "degraded"
+            && health["reasons"] == json!(["chat_ctx_size_mismatch", "prompt_template_mismatch"])
+    };
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_health()
+            .iter()
+            .any(|request| degraded(request.json())))
+        .await,
+        "no degraded health received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+
+    let warnings = &hub.device_info()[0].json()["warnings"];
+    assert_eq!(warnings.as_array().unwrap().len(), 2);
+    assert_eq!(warnings[0]["reason"], "chat_ctx_size_mismatch");
+    assert!(warnings[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("chat_ctx_size 4096"));
+    assert_eq!(warnings[1]["reason"], "prompt_template_mismatch");
+    assert!(gaias.log().contains("prompt_template_mismatch"));
+}
+
+#[tokio::test]
+async fn api_server_is_reached_on_unix_socket() {
+    let socket_dir = tempfile::tempdir().unwrap();
+    let api = MockApiServer::start_unix(&socket_dir.path().join("llamaedge.sock")).await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()
+            && !hub.device_health().is_empty())
+        .await,
+        "no notifications received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_health()[0].json(), json!({ "health": true }));
+    assert!(!api.chat_requests().is_empty());
+}
+
+#[tokio::test]
+async fn api_key_is_sent_as_bearer_token() {
+    let api = MockApiServer::start().await;
+    api.set_api_key("gaia-0123456789");
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write("api-key", "gaia-0123456789\n");
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()
+            && !hub.device_health().is_empty())
+        .await,
+        "no notifications received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_health()[0].json(), json!({ "health": true }));
+    assert_eq!(
+        api.chat_requests()[0].header("authorization"),
+        Some("Bearer gaia-0123456789")
+    );
+}
+
+#[tokio::test]
+async fn rejected_api_key_reports_auth_misconfigured() {
+    let api = MockApiServer::start().await;
+    api.set_api_key("gaia-0123456789");
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        "api_key = \"gaia-wrong\"\n\n[notifications]\nschema = \"v2\"\n",
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_health()
+            .iter()
+            .any(|request| request.json()["reasons"] == json!(["auth_misconfigured"])))
+        .await,
+        "no auth_misconfigured reported. gaias log:\n{}",
+        gaias.log()
+    );
+    let health = hub.device_health().last().unwrap().json();
+    assert_eq!(health["status"], "unhealthy");
+    assert_eq!(health["reasons"], json!(["auth_misconfigured"]));
+}
+
+#[tokio::test]
+async fn payloads_are_signed_with_the_node_key() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.set_node_key();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()
+            && hub.device_health().len() >= 2)
+        .await,
+        "no notifications received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+
+    let mut nonces = Vec::new();
+    for request in hub.device_info().iter().chain(hub.device_health().iter()) {
+        let signature =
+            PayloadSignature::from_headers(|name| request.header(name).map(str::to_string))
+                .unwrap();
+        assert_eq!(signature.address, NODE_ADDRESS);
+        assert_eq!(signature.verify(&request.body), Ok(()));
+        nonces.push(signature.nonce);
+    }
+    nonces.sort();
+    nonces.dedup();
+    assert_eq!(nonces.len(), hub.requests().len());
+}
+
+#[tokio::test]
+async fn payloads_are_unsigned_without_nodeid() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_health().is_empty()).await,
+        "no health notification received. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(hub.device_health()[0].header(ADDRESS_HEADER), None);
+    assert!(gaias.log().contains("the payloads are sent unsigned"));
+}
+
+#[tokio::test]
+async fn webhooks_are_signed_with_the_subscriber_secret() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let receiver = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        &format!(
+            "[[subscribers]]\nurl = \"{0}/events\"\nsecret = \"whsec_0123456789\"\n\n[[subscribers]]\nurl = \"{0}/status\"\ntopics = [\"health\"]\n",
+            receiver.url()
+        ),
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || {
+            let events = receiver.posts_to("/events");
+            events
+                .iter()
+                .any(|request| request.json()["api_server"].is_object())
+                && events
+                    .iter()
+                    .any(|request| request.json()["health"] == true)
+                && !receiver.posts_to("/status").is_empty()
+                && !hub.device_health().is_empty()
+        })
+        .await,
+        "no webhooks received. gaias log:\n{}",
+        gaias.log()
+    );
+
+    for request in receiver.posts_to("/events") {
+        let header = request.header(webhook::SIGNATURE_HEADER).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(
+            webhook::verify(
+                b"whsec_0123456789",
+                header,
+                &request.body,
+                now,
+                webhook::DEFAULT_TOLERANCE
+            ),
+            Ok(())
+        );
+    }
+
+    // only the health is sent to the second subscriber, unsigned
+    let status = receiver.posts_to("/status");
+    assert!(status
+        .iter()
+        .all(|request| request.json()["health"] == true));
+    assert_eq!(status[0].header(webhook::SIGNATURE_HEADER), None);
+    // nor is the hub signed with the secrets
+    assert_eq!(
+        hub.device_health()[0].header(webhook::SIGNATURE_HEADER),
+        None
+    );
+}
+
+#[tokio::test]
+async fn payloads_are_sent_as_cloudevents() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let receiver = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        &format!(
+            "[[subscribers]]\nurl = \"{0}/structured\"\nencoding = \"cloudevents_structured\"\n\n[[subscribers]]\nurl = \"{0}/binary\"\nencoding = \"cloudevents_binary\"\nsecret = \"whsec_0123456789\"\n",
+            receiver.url()
+        ),
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let has_type = |requests: &[RecordedRequest], event_type: &str| {
+        requests.iter().any(|request| {
+            request.json()["type"] == event_type || request.header("ce-type") == Some(event_type)
+        })
+    };
+    assert!(
+        common::wait_until(TIMEOUT, || {
+            let structured = receiver.posts_to("/structured");
+            let binary = receiver.posts_to("/binary");
+            has_type(&structured, "net.gaianet.node.info.updated")
+                && has_type(&structured, "net.gaianet.node.health.changed")
+                && has_type(&binary, "net.gaianet.node.info.updated")
+                && has_type(&binary, "net.gaianet.node.health.changed")
+                && !hub.device_health().is_empty()
+        })
+        .await,
+        "no events received. gaias log:\n{}",
+        gaias.log()
+    );
+
+    // structured mode: the payload is the data of the event
+    for request in receiver.posts_to("/structured") {
+        assert_eq!(
+            request.header("content-type"),
+            Some("application/cloudevents+json")
+        );
+        let event = request.json();
+        assert_eq!(event["specversion"], "1.0");
+        assert_eq!(event["source"], DEVICE_ID);
+        assert_eq!(event["datacontenttype"], "application/json");
+        assert!(event["id"].as_str().is_some_and(|id| !id.is_empty()));
+        match event["type"].as_str().unwrap() {
+            "net.gaianet.node.info.updated" => assert!(event["data"]["api_server"].is_object()),
+            _ => assert_eq!(event["data"]["health"], true),
+        }
+    }
+
+    // binary mode: the attributes are headers, the payload the body, signed as sent
+    for request in receiver.posts_to("/binary") {
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.header("ce-specversion"), Some("1.0"));
+        assert_eq!(request.header("ce-source"), Some(DEVICE_ID));
+        assert!(request.header("ce-id").is_some());
+        assert!(request.header("ce-time").is_some());
+        let payload = request.json();
+        assert!(payload["api_server"].is_object() || payload["health"] == true);
+        let header = request.header(webhook::SIGNATURE_HEADER).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(
+            webhook::verify(
+                b"whsec_0123456789",
+                header,
+                &request.body,
+                now,
+                webhook::DEFAULT_TOLERANCE
+            ),
+            Ok(())
+        );
+    }
+
+    // the hub keeps the raw JSON
+    assert_eq!(hub.device_health()[0].json()["health"], true);
+    assert_eq!(hub.device_health()[0].header("ce-type"), None);
+}
+
+#[tokio::test]
+async fn retried_events_keep_their_id() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let receiver = MockHub::start().await;
+    receiver.set_status(500);
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        &format!(
+            "[hub]\nretries = 3\n\n[[subscribers]]\nurl = \"{0}/a\"\nencoding = \"cloudevents_binary\"\n\n[[subscribers]]\nurl = \"{0}/b\"\nencoding = \"cloudevents_binary\"\n",
+            receiver.url()
+        ),
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let info_posts = |path: &str| -> Vec<RecordedRequest> {
+        receiver
+            .posts_to(path)
+            .into_iter()
+            .filter(|request| request.header("ce-type") == Some("net.gaianet.node.info.updated"))
+            .collect()
+    };
+    assert!(
+        common::wait_until(TIMEOUT, || info_posts("/a").len() >= 3
+            && info_posts("/b").len() >= 3)
+        .await,
+        "server info not retried. gaias log:\n{}",
+        gaias.log()
+    );
+
+    // all the attempts of the first event, to both subscribers, are the same event
+    let first = &info_posts("/a")[0];
+    let id = first.header("ce-id").unwrap();
+    let time = first.header("ce-time").unwrap();
+    for path in ["/a", "/b"] {
+        let attempts: Vec<_> = info_posts(path)
+            .into_iter()
+            .filter(|request| request.header("ce-id") == Some(id))
+            .collect();
+        assert_eq!(attempts.len(), 3, "{}", path);
+        assert!(attempts
+            .iter()
+            .all(|request| request.header("ce-time") == Some(time)));
+    }
+}
+
+#[tokio::test]
+async fn payloads_are_pushed_to_all_hubs() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let staging = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        &format!(
+            "[hub]\nextra_urls = [\"{}/v2/{{domain}}/{{device_id}}/{{topic}}\"]\n",
+            staging.url()
+        ),
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let info_path = format!("/v2/{}/{}/info", DOMAIN, DEVICE_ID);
+    let health_path = format!("/v2/{}/{}/health", DOMAIN, DEVICE_ID);
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_health().is_empty()
+            && !staging.posts_to(&info_path).is_empty()
+            && !staging.posts_to(&health_path).is_empty())
+        .await,
+        "no notifications received by the hubs. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(
+        staging.posts_to(&info_path)[0].json(),
+        hub.device_info()[0].json()
+    );
+}
+
+#[tokio::test]
+async fn hub_is_reached_through_the_proxy() {
+    let api = MockApiServer::start().await;
+    let proxy = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        &format!(
+            "[hub]\nallow_http = true\n\n[http]\nproxy = \"{}\"\n",
+            proxy.url()
+        ),
+    );
+
+    // the hub is only reachable through the proxy, the API server is reached directly
+    let (mut command, log_dir) =
+        Gaias::command_with_hub_url(&gaianet, &api, "http://hub.gaianet.invalid");
+    command.args(["--interval", "1"]);
+    let gaias = Gaias::spawn_command(command, log_dir);
+
+    let health_url = format!("http://hub.gaianet.invalid/device-health/{}", DEVICE_ID);
+    assert!(
+        common::wait_until(TIMEOUT, || !proxy.posts_to(&health_url).is_empty()).await,
+        "no health notification received by the proxy. gaias log:\n{}",
+        gaias.log()
+    );
+    assert_eq!(
+        proxy.posts_to(&health_url)[0].json(),
+        json!({ "health": true })
+    );
+    assert!(!api.chat_requests().is_empty());
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use common::{ChatReply, GaianetDir, MockApiServer, MockHub, DEVICE_ID};
+use serde_json::json;
+use server_assistant::{error::AssistantError, Assistant, Event};
+use std::time::Duration;
+use tokio::sync::broadcast::Receiver;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+async fn next_event(events: &mut Receiver<Event>) -> Event {
+    tokio::time::timeout(TIMEOUT, events.recv())
+        .await
+        .expect("no event received")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn embedded_assistant_emits_events() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .interval(1)
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(assistant.server_health().await, None);
+    assert_eq!(assistant.server_info().await, None);
+
+    let mut events = assistant.subscribe();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+
+    let mut received = [next_event(&mut events).await, next_event(&mut events).await];
+    received.sort_by_key(|event| matches!(event, Event::InfoUpdated(_)));
+    assert_eq!(received[0], Event::HealthChanged { healthy: true });
+    let Event::InfoUpdated(info) = &received[1] else {
+        panic!("expected server info, got {:?}", received[1]);
+    };
+    assert_eq!(
+        info["extras"]["system_prompt"],
+        "You are a helpful assistant."
+    );
+
+    assert_eq!(assistant.server_health().await, Some(true));
+    assert_eq!(assistant.server_info().await.as_ref(), Some(info));
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await,
+        "server info not pushed to the hub"
+    );
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn explicit_settings_without_gaianet_dir() {
+    let api = MockApiServer::start().await;
+    api.set_chat_reply(ChatReply::QdrantError);
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let info_url = format!("{}/info", hub.url());
+    let health_url = format!("{}/health", hub.url());
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .server_log_file(gaianet.join("log/start-llamaedge.log"))
+        .system_prompt("Be brief.")
+        .info_subscriber(&info_url)
+        .health_subscriber(&health_url)
+        .interval(1)
+        .build()
+        .await
+        .unwrap();
+
+    let mut events = assistant.subscribe();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+
+    loop {
+        if next_event(&mut events).await == (Event::HealthChanged { healthy: false }) {
+            break;
+        }
+    }
+    assert!(
+        common::wait_until(TIMEOUT, || {
+            let requests = hub.requests();
+            requests.iter().any(|request| request.path == "/info")
+                && requests.iter().any(|request| request.path == "/health")
+        })
+        .await
+    );
+
+    let requests = hub.requests();
+    let info = requests.iter().find(|r| r.path == "/info").unwrap().json();
+    assert_eq!(info["extras"]["system_prompt"], "Be brief.");
+    let health = requests
+        .iter()
+        .find(|r| r.path == "/health")
+        .unwrap()
+        .json();
+    assert_eq!(health, json!({ "health": false }));
+    assert!(requests
+        .iter()
+        .all(|request| !request.path.contains(DEVICE_ID)));
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn failed_health_check_stops_the_assistant() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .interval(1)
+        .build()
+        .await
+        .unwrap();
+    gaianet.remove("log/start-llamaedge.log");
+
+    let result = tokio::time::timeout(TIMEOUT, assistant.run())
+        .await
+        .expect("the assistant kept running");
+
+    assert!(matches!(result, Err(AssistantError::Operation(_))));
+}
+
+#[tokio::test]
+async fn log_file_is_required_without_gaianet_dir() {
+    let result = Assistant::builder().build().await;
+
+    assert!(matches!(result, Err(AssistantError::ArgumentError(_))));
+}
+
+#[tokio::test]
+async fn invalid_gaianet_dir_is_rejected() {
+    let gaianet = GaianetDir::new();
+    gaianet.remove("config.json");
+
+    let result = Assistant::builder()
+        .gaianet_dir(gaianet.path())
+        .build()
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(AssistantError::ArgumentError(msg)) if msg.contains("config.json")
+    ));
+}
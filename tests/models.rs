@@ -0,0 +1,406 @@
+mod common;
+
+use common::{GaianetDir, Gaias, MockApiServer, MockHub, CHAT_MODEL, EMBEDDING_MODEL};
+use server_assistant::{Assistant, Event, ModelKind};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+fn sha256_of(gaianet: &GaianetDir, model: &str) -> String {
+    sha256::try_digest(gaianet.join(model).as_path()).unwrap()
+}
+
+#[tokio::test]
+async fn model_hashes_are_pushed_once_computed() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(CHAT_MODEL, &"chat weights ".repeat(100_000));
+    gaianet.write(EMBEDDING_MODEL, "embedding weights");
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let hashed = |info: &serde_json::Value| {
+        info["chat_model"]["sha256"].is_string() && info["embedding_model"]["sha256"].is_string()
+    };
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_info()
+            .iter()
+            .any(|request| hashed(&request.json())))
+        .await,
+        "model hashes not pushed. gaias log:\n{}",
+        gaias.log()
+    );
+
+    let info = hub.device_info().last().unwrap().json();
+    assert_eq!(
+        info["chat_model"]["sha256"],
+        sha256_of(&gaianet, CHAT_MODEL)
+    );
+    assert_eq!(
+        info["embedding_model"]["sha256"],
+        sha256_of(&gaianet, EMBEDDING_MODEL)
+    );
+    assert!(gaias.log().contains("Hashing the chat model: 100%"));
+
+    let cache: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(gaianet.join("assistant-hashes.json")).unwrap(),
+    )
+    .unwrap();
+    let cached = &cache["models"][gaianet.join(CHAT_MODEL).to_str().unwrap()];
+    assert_eq!(cached["sha256"], sha256_of(&gaianet, CHAT_MODEL));
+    assert!(cached["size"].is_u64() && cached["inode"].is_u64());
+}
+
+#[tokio::test]
+async fn cached_hashes_are_reused_until_the_model_changes() {
+    let api = MockApiServer::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(CHAT_MODEL, "chat weights");
+
+    // first run computes the hash
+    let hub = MockHub::start().await;
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || gaianet.join("assistant-hashes.json").exists()).await,
+        "hash cache not written. gaias log:\n{}",
+        gaias.log()
+    );
+    drop(gaias);
+
+    // the second run pushes the cached hash right away
+    let hub = MockHub::start().await;
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await);
+    assert_eq!(
+        hub.device_info()[0].json()["chat_model"]["sha256"],
+        sha256_of(&gaianet, CHAT_MODEL)
+    );
+    assert!(gaias.log().contains("sha256 of chat model (cached)"));
+    assert!(!gaias.log().contains("Computing sha256 of the chat model"));
+    drop(gaias);
+
+    // a replaced model is hashed again
+    gaianet.write(CHAT_MODEL, "other chat weights");
+    let hub = MockHub::start().await;
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    let expected = sha256_of(&gaianet, CHAT_MODEL);
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_info()
+            .iter()
+            .any(|request| request.json()["chat_model"]["sha256"] == expected))
+        .await,
+        "new hash not pushed. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(gaias.log().contains("Computing sha256 of the chat model"));
+}
+
+#[tokio::test]
+async fn replaced_model_is_hashed_and_pushed_again() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(CHAT_MODEL, "chat weights");
+    let old_sha256 = sha256_of(&gaianet, CHAT_MODEL);
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_info()
+            .iter()
+            .any(|request| request.json()["chat_model"]["sha256"] == old_sha256))
+        .await,
+        "model hash not pushed. gaias log:\n{}",
+        gaias.log()
+    );
+
+    gaianet.write(CHAT_MODEL, "fine-tuned chat weights");
+    let new_sha256 = sha256_of(&gaianet, CHAT_MODEL);
+
+    assert!(
+        common::wait_until(TIMEOUT, || {
+            hub.device_info()
+                .last()
+                .is_some_and(|request| request.json()["chat_model"]["sha256"] == new_sha256)
+        })
+        .await,
+        "new model hash not pushed. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(gaias.log().contains(&format!(
+        "model_changed: chat model {}: {} -> {}",
+        gaianet.join(CHAT_MODEL).display(),
+        old_sha256,
+        new_sha256
+    )));
+}
+
+#[tokio::test]
+async fn model_changes_are_emitted() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(EMBEDDING_MODEL, "embedding weights");
+    let old_sha256 = sha256_of(&gaianet, EMBEDDING_MODEL);
+
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .build()
+        .await
+        .unwrap();
+    let mut events = assistant.subscribe();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+    assert!(
+        common::wait_until(TIMEOUT, || gaianet.join("assistant-hashes.json").exists()).await,
+        "model not hashed"
+    );
+
+    // a touched model with the same content is not reported
+    gaianet.write(EMBEDDING_MODEL, "embedding weights");
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    gaianet.remove(EMBEDDING_MODEL);
+
+    let changed = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            if let Event::ModelChanged {
+                kind,
+                old_sha256,
+                new_sha256,
+                ..
+            } = events.recv().await.unwrap()
+            {
+                return (kind, old_sha256, new_sha256);
+            }
+        }
+    })
+    .await
+    .expect("no model change emitted");
+    assert_eq!(changed, (ModelKind::Embedding, Some(old_sha256), None));
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn mismatching_model_reports_unhealthy() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let mut config = common::default_config();
+    config["chat_sha256"] = "0".repeat(64).into();
+    let gaianet = GaianetDir::with_config(config);
+    gaianet.write(CHAT_MODEL, "corrupted chat weights");
+    gaianet.write("assistant.toml", "[notifications]\nschema = \"v2\"\n");
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    let unhealthy = |health: serde_json::Value| {
+        health["status"] == "unhealthy"
+            && health["reasons"] == serde_json::json!(["model_hash_mismatch"])
+    };
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_health()
+            .iter()
+            .any(|request| unhealthy(request.json())))
+        .await,
+        "mismatch not reported. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(gaias.log().contains(&format!(
+        "model_hash_mismatch: chat model {}: expected {}, found {}",
+        gaianet.join(CHAT_MODEL).display(),
+        "0".repeat(64),
+        sha256_of(&gaianet, CHAT_MODEL)
+    )));
+}
+
+#[tokio::test]
+async fn models_lock_takes_precedence_over_config_json() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let mut config = common::default_config();
+    config["chat_sha256"] = "0".repeat(64).into();
+    let gaianet = GaianetDir::with_config(config.clone());
+    gaianet.write(CHAT_MODEL, "chat weights");
+    gaianet.write(
+        "models.lock",
+        &format!(
+            "[[model]]\nurl = \"{}\"\nsha256 = \"{}\"\n",
+            config["chat"].as_str().unwrap(),
+            sha256_of(&gaianet, CHAT_MODEL).to_uppercase()
+        ),
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+
+    assert!(
+        common::wait_until(TIMEOUT, || gaias
+            .log()
+            .contains("The chat model matches its expected sha256"))
+        .await,
+        "model not verified. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(common::wait_until(TIMEOUT, || hub.device_health().len() >= 2).await);
+    assert!(hub
+        .device_health()
+        .iter()
+        .all(|request| request.json() == serde_json::json!({ "health": true })));
+}
+
+#[tokio::test]
+async fn scheduled_verification_catches_tampering_in_place() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(CHAT_MODEL, "chat weights");
+    let mut config = common::default_config();
+    config["chat_sha256"] = sha256_of(&gaianet, CHAT_MODEL).into();
+    gaianet.write(
+        "config.json",
+        &serde_json::to_string_pretty(&config).unwrap(),
+    );
+    gaianet.write(
+        "assistant.toml",
+        "[models]\nverify_interval = 1\n\n[notifications]\nschema = \"v2\"\n",
+    );
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || gaias
+            .log()
+            .contains("The chat model matches its expected sha256"))
+        .await,
+        "model not verified. gaias log:\n{}",
+        gaias.log()
+    );
+
+    // same size, modification time and inode
+    let model = gaianet.join(CHAT_MODEL);
+    let times = gaianet.join("times");
+    let touch = |args: &[&std::path::Path]| {
+        assert!(std::process::Command::new("touch")
+            .arg("-r")
+            .args(args)
+            .status()
+            .unwrap()
+            .success());
+    };
+    touch(&[&model, &times]);
+    std::fs::write(&model, "chat weighTs").unwrap();
+    touch(&[&times, &model]);
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_health().last().is_some_and(
+            |request| request.json()["reasons"] == serde_json::json!(["model_hash_mismatch"])
+        ))
+        .await,
+        "tampering not reported. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(gaias.log().contains("Verify the chat model"));
+    assert_eq!(
+        hub.device_info().last().unwrap().json()["chat_model"]["sha256"],
+        sha256_of(&gaianet, CHAT_MODEL)
+    );
+}
+
+#[tokio::test]
+async fn deleted_model_reports_unhealthy() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(CHAT_MODEL, "chat weights");
+    let mut config = common::default_config();
+    config["chat_sha256"] = sha256_of(&gaianet, CHAT_MODEL).into();
+    gaianet.write(
+        "config.json",
+        &serde_json::to_string_pretty(&config).unwrap(),
+    );
+    gaianet.write("assistant.toml", "[notifications]\nschema = \"v2\"\n");
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || gaias
+            .log()
+            .contains("The chat model matches its expected sha256"))
+        .await,
+        "model not verified. gaias log:\n{}",
+        gaias.log()
+    );
+
+    gaianet.remove(CHAT_MODEL);
+
+    let missing = |health: serde_json::Value| {
+        health["status"] == "unhealthy" && health["reasons"] == serde_json::json!(["model_missing"])
+    };
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_health()
+            .last()
+            .is_some_and(|request| missing(request.json())))
+        .await,
+        "missing model not reported. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(gaias.log().contains(&format!(
+        "model_missing: chat model {} is missing or unreadable",
+        gaianet.join(CHAT_MODEL).display()
+    )));
+
+    // the health stays unhealthy until the model is restored
+    let pushed = hub.device_health().len();
+    assert!(common::wait_until(TIMEOUT, || hub.device_health().len() >= pushed + 2).await);
+    assert!(missing(hub.device_health().last().unwrap().json()));
+}
+
+#[tokio::test]
+async fn gguf_metadata_is_added_to_server_info() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write_bytes(
+        CHAT_MODEL,
+        &common::gguf_model(
+            &[
+                ("general.architecture", "llama"),
+                ("tokenizer.ggml.model", "gpt2"),
+            ],
+            &[
+                ("general.file_type", 17),
+                ("llama.context_length", 8192),
+                ("llama.embedding_length", 4096),
+            ],
+            &[&[4096, 1000], &[4096]],
+        ),
+    );
+    gaianet.write(EMBEDDING_MODEL, "not a gguf file");
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await,
+        "no server info pushed. gaias log:\n{}",
+        gaias.log()
+    );
+
+    let info = hub.device_info()[0].json();
+    assert_eq!(
+        info["chat_model"]["gguf"],
+        serde_json::json!({
+            "version": 3,
+            "architecture": "llama",
+            "parameter_count": 4096 * 1000 + 4096,
+            "quantization": "Q5_K_M",
+            "context_length": 8192,
+            "embedding_length": 4096,
+            "tokenizer": "gpt2"
+        })
+    );
+    assert!(info["embedding_model"].get("gguf").is_none());
+    assert!(gaias.log().contains("Failed to read the GGUF header"));
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::{ChatReply, GaianetDir, Gaias, MockApiServer, MockHub};
+use serde_json::{json, Value};
+use std::{
+    process::{Output, Stdio},
+    time::Duration,
+};
+
+async fn probe_once(
+    gaianet: &GaianetDir,
+    api: &MockApiServer,
+    hub: &MockHub,
+    format: &str,
+) -> Output {
+    let (mut command, _log_dir) = Gaias::command(gaianet, api, hub);
+    command
+        .args(["probe", "--once", "--format", format])
+        .stdout(Stdio::piped())
+        .output()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn healthy_server_exits_with_0() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let output = probe_once(&gaianet, &api, &hub, "json").await;
+
+    assert_eq!(output.status.code(), Some(0));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["status"], "healthy");
+    assert_eq!(report["reasons"], json!([]));
+    assert_eq!(report["probe_status"], 200);
+    assert_eq!(api.chat_requests().len(), 1);
+    assert!(hub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn config_mismatch_exits_with_1() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let mut config = common::default_config();
+    config["chat_ctx_size"] = json!("4096");
+    let gaianet = GaianetDir::with_config(config);
+
+    let output = probe_once(&gaianet, &api, &hub, "nagios").await;
+
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("GAIAS WARNING - degraded: chat_ctx_size_mismatch | latency="),
+        "{}",
+        stdout
+    );
+}
+
+#[tokio::test]
+async fn failing_server_exits_with_2() {
+    let api = MockApiServer::start().await;
+    api.set_chat_reply(ChatReply::QdrantError);
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let output = probe_once(&gaianet, &api, &hub, "human").await;
+
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Status: unhealthy"), "{}", stdout);
+    assert!(
+        stdout.contains("Reasons: vector_database_error"),
+        "{}",
+        stdout
+    );
+    assert!(hub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn invalid_config_exits_with_3() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write("assistant.toml", "interval = \"often\"\n");
+
+    let output = probe_once(&gaianet, &api, &hub, "nagios").await;
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(api.chat_requests().is_empty());
+    assert!(hub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn repeated_probes_need_an_interval() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write("assistant.toml", "interval = 0\n");
+
+    let (mut command, _log_dir) = Gaias::command(&gaianet, &api, &hub);
+    let output = tokio::time::timeout(
+        Duration::from_secs(20),
+        command
+            .arg("probe")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await
+    .expect("probing without a pause")
+    .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("interval"));
+    assert!(api.chat_requests().is_empty());
+}
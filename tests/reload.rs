@@ -0,0 +1,454 @@
+mod common;
+
+use common::{GaianetDir, Gaias, MockApiServer, MockHub, RecordedRequest, CHAT_MODEL, DEVICE_ID};
+use serde_json::json;
+use server_assistant::{
+    config::{AssistantConfig, PayloadEncoding, SubscriberConfig, Topic},
+    webhook, Assistant, Event, ModelKind,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[tokio::test]
+async fn device_id_change_pushes_info_to_new_url() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .interval(1)
+        .build()
+        .await
+        .unwrap();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+
+    assert!(common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await);
+
+    gaianet.set_device_id("device-new");
+    let mut events = assistant.subscribe();
+    assistant.reload().await.unwrap();
+
+    assert_eq!(events.recv().await.unwrap(), Event::Reloaded);
+    assert_eq!(hub.posts_to("/device-info/device-new").len(), 1);
+    assert_eq!(hub.device_info().len(), 1);
+    assert!(
+        common::wait_until(TIMEOUT, || !hub
+            .posts_to("/device-health/device-new")
+            .is_empty())
+        .await
+    );
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn unchanged_node_settings_do_not_push_info() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .interval(1)
+        .build()
+        .await
+        .unwrap();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+    assert!(common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await);
+
+    assistant.reload().await.unwrap();
+
+    assert_eq!(hub.device_info().len(), 1);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn interval_change_rearms_tickers() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let mut config = AssistantConfig {
+        server_socket_addr: api.addr().into(),
+        interval: 3600,
+        ..Default::default()
+    };
+    config.hub.url = hub.url();
+    let source = Arc::new(Mutex::new(config.clone()));
+    let loader_source = Arc::clone(&source);
+
+    let assistant = Assistant::builder()
+        .config(config)
+        .config_loader(Arc::new(move || Ok(loader_source.lock().unwrap().clone())))
+        .gaianet_dir(gaianet.path())
+        .build()
+        .await
+        .unwrap();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+
+    // the health is not pushed before it is known, then not for an hour
+    assert!(common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(hub.device_health().is_empty());
+
+    source.lock().unwrap().interval = 1;
+    assistant.reload().await.unwrap();
+
+    assert!(common::wait_until(TIMEOUT, || hub.device_health().len() >= 2).await);
+    assert_eq!(assistant.config().await.interval, 1);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn invalid_config_keeps_previous_settings() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .config_loader(Arc::new(|| {
+            AssistantConfig::from_file("/nonexistent/assistant.toml")
+        }))
+        .build()
+        .await
+        .unwrap();
+
+    assert!(assistant.reload().await.is_err());
+    assert_eq!(assistant.config().await.hub.url, hub.url());
+}
+
+#[tokio::test]
+async fn sighup_reloads_config_file() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write("assistant.toml", "interval = 3600\n");
+
+    let gaias = Gaias::spawn_with_args(&gaianet, &api, &hub, &[]);
+    // SIGHUP terminates the process until the handler is installed
+    assert!(
+        common::wait_until(TIMEOUT, || gaias.log().contains("Watch for changes")).await,
+        "reload not enabled. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await);
+
+    gaias.signal("HUP");
+
+    assert!(
+        common::wait_until(TIMEOUT, || gaias.log().contains("Received SIGHUP")).await,
+        "SIGHUP not handled. gaias log:\n{}",
+        gaias.log()
+    );
+
+    std::fs::write(gaianet.join("assistant.toml"), "interval = 1\n").unwrap();
+    gaias.signal("HUP");
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_health().len() >= 2).await,
+        "interval not reloaded. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(gaias.log().contains("Reload the configuration"));
+}
+
+#[tokio::test]
+async fn frpc_toml_change_is_picked_up() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await,
+        "no server info received. gaias log:\n{}",
+        gaias.log()
+    );
+
+    gaianet.set_device_id("device-replaced");
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub
+            .posts_to("/device-info/device-replaced")
+            .is_empty())
+        .await,
+        "server info not pushed for the new device ID. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(hub
+        .requests()
+        .iter()
+        .filter(|request| request.path.ends_with(DEVICE_ID))
+        .all(|request| request.path.starts_with("/device-")));
+}
+
+#[tokio::test]
+async fn model_url_change_is_hashed_before_verified() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(CHAT_MODEL, "chat weights");
+    gaianet.write("chat-v2.gguf", "chat weights v2");
+    let sha256_of = |model: &str| sha256::try_digest(gaianet.join(model).as_path()).unwrap();
+    let (old_sha256, new_sha256) = (sha256_of(CHAT_MODEL), sha256_of("chat-v2.gguf"));
+    let mut config = common::default_config();
+    config["chat_sha256"] = old_sha256.clone().into();
+    gaianet.write("config.json", &config.to_string());
+
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .interval(1)
+        .build()
+        .await
+        .unwrap();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_info().iter().any(|request| request
+            .json()["chat_model"]["sha256"]
+            == old_sha256.as_str()))
+        .await,
+        "model hash not pushed"
+    );
+
+    let pushed = hub.device_info().len();
+    config["chat"] = "https://huggingface.co/gaianet/chat/resolve/main/chat-v2.gguf".into();
+    config["chat_sha256"] = new_sha256.clone().into();
+    gaianet.write("config.json", &config.to_string());
+    let mut events = assistant.subscribe();
+    assistant.reload().await.unwrap();
+
+    // the new model is hashed, and never compared with the hash of the previous one
+    let changed = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            match events.recv().await.unwrap() {
+                Event::ModelChanged {
+                    kind, new_sha256, ..
+                } => return (kind, new_sha256),
+                Event::ModelHashMismatch { .. } => panic!("spurious model hash mismatch"),
+                _ => {}
+            }
+        }
+    })
+    .await
+    .expect("new model not hashed");
+    assert_eq!(changed, (ModelKind::Chat, Some(new_sha256.clone())));
+    assert!(
+        common::wait_until(TIMEOUT, || hub.device_info().last().unwrap().json()
+            ["chat_model"]["sha256"]
+            == new_sha256.as_str())
+        .await
+    );
+    assert!(hub.device_info()[pushed..]
+        .iter()
+        .all(|request| request.json()["chat_model"]["sha256"] != old_sha256.as_str()));
+    assert!(hub
+        .device_health()
+        .iter()
+        .all(|request| request.json()["health"] == true));
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn rotated_api_key_is_sent_without_restart() {
+    let api = MockApiServer::start().await;
+    api.set_api_key("gaia-0123456789");
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+    gaianet.write(
+        "assistant.toml",
+        "[notifications]\nschema = \"v2\"\n\n[schedules]\nprobe = \"1s\"\n",
+    );
+    gaianet.write("api-key", "gaia-wrong\n");
+
+    let gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_health()
+            .iter()
+            .any(|request| request.json()["reasons"] == json!(["auth_misconfigured"])))
+        .await,
+        "no auth_misconfigured reported. gaias log:\n{}",
+        gaias.log()
+    );
+
+    gaianet.write("api-key", "gaia-0123456789\n");
+
+    assert!(
+        common::wait_until(TIMEOUT, || hub
+            .device_health()
+            .last()
+            .is_some_and(|request| request.json()["status"] == "healthy"))
+        .await,
+        "rotated API key not applied. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(
+        common::wait_until(TIMEOUT, || api.chat_requests().iter().any(
+            |request| request.header("authorization") == Some("Bearer gaia-0123456789")
+        ))
+        .await,
+        "rotated API key not sent by the probe. gaias log:\n{}",
+        gaias.log()
+    );
+    assert!(!gaias.log().contains("gaia-0123456789"));
+}
+
+#[tokio::test]
+async fn subscriber_changes_apply_without_restart() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let receiver = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let mut config = AssistantConfig {
+        server_socket_addr: api.addr().into(),
+        interval: 1,
+        ..Default::default()
+    };
+    config.hub.url = hub.url();
+    config.subscribers.push(SubscriberConfig {
+        url: format!("{}/events", receiver.url()),
+        topics: vec![Topic::Health],
+        secret: Some("whsec_old".to_string()),
+        encoding: PayloadEncoding::Json,
+    });
+    let source = Arc::new(Mutex::new(config.clone()));
+    let loader_source = Arc::clone(&source);
+
+    let assistant = Assistant::builder()
+        .config(config)
+        .config_loader(Arc::new(move || Ok(loader_source.lock().unwrap().clone())))
+        .gaianet_dir(gaianet.path())
+        .build()
+        .await
+        .unwrap();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+
+    let signed_with = |request: &RecordedRequest, secret: &str| {
+        request
+            .header(webhook::SIGNATURE_HEADER)
+            .is_some_and(|header| {
+                webhook::verify(
+                    secret.as_bytes(),
+                    header,
+                    &request.body,
+                    chrono::Utc::now().timestamp(),
+                    webhook::DEFAULT_TOLERANCE,
+                )
+                .is_ok()
+            })
+    };
+    assert!(common::wait_until(TIMEOUT, || !receiver.posts_to("/events").is_empty()).await);
+    assert!(receiver
+        .posts_to("/events")
+        .iter()
+        .all(|request| signed_with(request, "whsec_old")));
+
+    // rotate the secret, and add a subscriber to the server information
+    {
+        let mut source = source.lock().unwrap();
+        source.subscribers[0].secret = Some("whsec_new".to_string());
+        source.subscribers.push(SubscriberConfig {
+            url: format!("{}/info", receiver.url()),
+            topics: vec![Topic::Info],
+            secret: None,
+            encoding: PayloadEncoding::Json,
+        });
+    }
+    assistant.reload().await.unwrap();
+    let rotated = receiver.posts_to("/events").len();
+
+    // a notification in flight may still carry the old signature
+    assert!(
+        common::wait_until(TIMEOUT, || receiver.posts_to("/events")[rotated..]
+            .iter()
+            .any(|request| signed_with(request, "whsec_new")))
+        .await
+    );
+    let events = receiver.posts_to("/events");
+    assert!(!signed_with(events.last().unwrap(), "whsec_old"));
+    assert!(receiver.posts_to("/info")[0].json()["api_server"].is_object());
+    assert_eq!(assistant.config().await.subscribers.len(), 2);
+
+    // a removed subscriber receives nothing more
+    source.lock().unwrap().subscribers.remove(0);
+    assistant.reload().await.unwrap();
+    let pushed = hub.device_health().len();
+    assert!(common::wait_until(TIMEOUT, || hub.device_health().len() > pushed).await);
+    let removed = receiver.posts_to("/events").len();
+    assert!(common::wait_until(TIMEOUT, || hub.device_health().len() >= pushed + 3).await);
+    assert_eq!(receiver.posts_to("/events").len(), removed);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn http_settings_apply_without_restart() {
+    let api = MockApiServer::start().await;
+    let proxy = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    // the hub is only reachable through the proxy, set on reload
+    let mut config = AssistantConfig {
+        server_socket_addr: api.addr().into(),
+        interval: 1,
+        ..Default::default()
+    };
+    config.hub.url = "http://hub.gaianet.invalid".to_string();
+    config.hub.allow_http = true;
+    let source = Arc::new(Mutex::new(config.clone()));
+    let loader_source = Arc::clone(&source);
+
+    let assistant = Assistant::builder()
+        .config(config)
+        .config_loader(Arc::new(move || Ok(loader_source.lock().unwrap().clone())))
+        .gaianet_dir(gaianet.path())
+        .build()
+        .await
+        .unwrap();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+
+    assert!(common::wait_until(TIMEOUT, || !api.chat_requests().is_empty()).await);
+    assert!(proxy.requests().is_empty());
+
+    source.lock().unwrap().http.proxy = Some(proxy.url());
+    assistant.reload().await.unwrap();
+
+    let health_url = format!("http://hub.gaianet.invalid/device-health/{}", DEVICE_ID);
+    assert!(common::wait_until(TIMEOUT, || !proxy.posts_to(&health_url).is_empty()).await);
+    assert_eq!(
+        assistant.config().await.http.proxy.as_deref(),
+        Some(proxy.url().as_str())
+    );
+
+    // invalid settings are rejected, keeping the current client
+    source.lock().unwrap().http.ca_cert = Some(gaianet.join("missing.pem"));
+    assert!(assistant.reload().await.is_err());
+    let pushed = proxy.posts_to(&health_url).len();
+    assert!(common::wait_until(TIMEOUT, || proxy.posts_to(&health_url).len() > pushed).await);
+
+    handle.abort();
+}
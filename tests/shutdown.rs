@@ -0,0 +1,96 @@
+mod common;
+
+use common::{GaianetDir, Gaias, MockApiServer, MockHub};
+use serde_json::json;
+use server_assistant::{Assistant, Event, ShutdownReason};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+async fn shuts_down_on(signal: &str, reason: &str) {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    let mut gaias = Gaias::spawn(&gaianet, &api, &hub);
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_health().is_empty()
+            && gaias.log().contains("Watch for changes"))
+        .await,
+        "no health received by the hub. gaias log:\n{}",
+        gaias.log()
+    );
+
+    gaias.signal(signal);
+
+    let status = gaias.wait(TIMEOUT).await.expect("gaias did not exit");
+    assert_eq!(status.code(), Some(0));
+    let health = hub.device_health();
+    assert_eq!(
+        health.last().unwrap().json(),
+        json!({ "health": false, "reason": reason })
+    );
+    assert!(health[..health.len() - 1]
+        .iter()
+        .all(|request| request.json() == json!({ "health": true })));
+}
+
+#[tokio::test]
+async fn sigterm_sends_final_notification() {
+    shuts_down_on("TERM", "assistant_shutdown").await;
+}
+
+#[tokio::test]
+async fn sigint_sends_final_notification() {
+    shuts_down_on("INT", "assistant_shutdown").await;
+}
+
+#[tokio::test]
+async fn sigusr1_reports_node_stopping() {
+    shuts_down_on("USR1", "node_stopping").await;
+}
+
+#[tokio::test]
+async fn embedded_assistant_returns_after_shutdown() {
+    let api = MockApiServer::start().await;
+    let hub = MockHub::start().await;
+    let gaianet = GaianetDir::new();
+
+    // the periodic notification is not due before the shutdown
+    let assistant = Assistant::builder()
+        .server_addr(api.addr())
+        .gaianet_dir(gaianet.path())
+        .hub_url(hub.url())
+        .interval(3600)
+        .build()
+        .await
+        .unwrap();
+    let mut events = assistant.subscribe();
+    let runner = assistant.clone();
+    let handle = tokio::spawn(async move { runner.run().await });
+
+    assert!(
+        common::wait_until(TIMEOUT, || !hub.device_info().is_empty()).await,
+        "server info not pushed to the hub"
+    );
+    assistant.shutdown(ShutdownReason::NodeStopping);
+
+    tokio::time::timeout(TIMEOUT, handle)
+        .await
+        .expect("run did not return")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        hub.device_health().last().unwrap().json(),
+        json!({ "health": false, "reason": "node_stopping" })
+    );
+    loop {
+        match events.try_recv().unwrap() {
+            Event::ShuttingDown(reason) => {
+                assert_eq!(reason, ShutdownReason::NodeStopping);
+                break;
+            }
+            _ => continue,
+        }
+    }
+}